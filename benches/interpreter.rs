@@ -0,0 +1,135 @@
+use criterion::black_box;
+use criterion::criterion_group;
+use criterion::criterion_main;
+use criterion::Criterion;
+
+use lox::environment::Environment;
+use lox::intern::intern;
+use lox::interpreter::Interpret;
+use lox::interpreter::Interpreter;
+use lox::object::LoxObject;
+use lox::optimizer;
+use lox::parser::Parser;
+use lox::scanning::Scanner;
+
+fn run(source: &str) {
+    let mut interpreter = Interpreter::new();
+    let statements = optimizer::fold_constants(Parser::from_source(source).parse().0);
+    for statement in statements {
+        statement.evaluate(&mut interpreter).unwrap();
+    }
+}
+
+fn scanning(c: &mut Criterion) {
+    let source = "var answer = (1 + 2.5) * \"text\" != nil and true; // comment\n".repeat(1000);
+    c.bench_function("scan 1000 lines", |b| {
+        b.iter(|| Scanner::new(black_box(&source)).scan_tokens())
+    });
+}
+
+fn parsing(c: &mut Criterion) {
+    let source = format!("{}1{};", "(1 + ".repeat(100), ")".repeat(100));
+    let tokens = Scanner::new(&source).scan_tokens().0;
+    c.bench_function("parse 100 nested groupings", |b| {
+        b.iter(|| {
+            Parser::new(&source, black_box(tokens.clone()).into_iter().map(Ok))
+                .parse()
+                .0
+        })
+    });
+}
+
+fn fibonacci(c: &mut Criterion) {
+    // functions are not implemented yet, so this computes fib(25) iteratively
+    let source = "
+        var a = 0;
+        var b = 1;
+        for (var i = 0; i < 25; i = i + 1) {
+            var next = a + b;
+            a = b;
+            b = next;
+        }
+    ";
+    c.bench_function("fib(25)", |b| b.iter(|| run(black_box(source))));
+}
+
+fn tight_loop(c: &mut Criterion) {
+    let source = "for (var i = 0; i < 100000; i = i + 1) {}";
+    c.bench_function("for loop 100000 iterations", |b| {
+        b.iter(|| run(black_box(source)))
+    });
+}
+
+fn variable_access(c: &mut Criterion) {
+    let source = "
+        var x = 1;
+        for (var i = 0; i < 1000000; i = i + 1) {
+            var y = x;
+        }
+    ";
+    let mut group = c.benchmark_group("variables");
+    group.sample_size(10);
+    group.bench_function("access 1000000 times", |b| {
+        b.iter(|| run(black_box(source)))
+    });
+    group.finish();
+}
+
+fn constant_folding(c: &mut Criterion) {
+    let source = "
+        var x;
+        var y;
+        var z;
+        for (var i = 0; i < 1000; i = i + 1) {
+            x = (1 + 2) * (3 - 4) / (5 + 6) - -(7 * 8);
+            y = \"con\" + \"cat\" + \"enated\";
+            z = 9 < 10 == !false;
+        }
+    ";
+    let mut group = c.benchmark_group("constant expressions in a loop");
+    group.bench_function("unfolded", |b| {
+        let statements = Parser::from_source(source).parse().0;
+        b.iter(|| {
+            let mut interpreter = Interpreter::new();
+            for statement in &statements {
+                statement.evaluate(&mut interpreter).unwrap();
+            }
+        })
+    });
+    group.bench_function("folded", |b| {
+        let (statements, _, _) = Parser::from_source(source).parse();
+        let statements = optimizer::fold_constants(statements);
+        b.iter(|| {
+            let mut interpreter = Interpreter::new();
+            for statement in &statements {
+                statement.evaluate(&mut interpreter).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+fn variable_lookup(c: &mut Criterion) {
+    let mut environment = Environment::new();
+    environment.define(intern("global"), LoxObject::Number(1.0));
+    for depth in 0..20 {
+        environment.new_scope();
+        environment.define(intern(&format!("local{}", depth)), LoxObject::Nil);
+    }
+    let name = intern("global");
+    c.bench_function("lookup through 20 scopes", |b| {
+        b.iter(|| environment.get(black_box(&name)))
+    });
+}
+
+criterion_group!(
+    benches,
+    scanning,
+    parsing,
+    fibonacci,
+    tight_loop,
+    variable_access,
+    constant_folding,
+    variable_lookup
+);
+criterion_main!(benches);
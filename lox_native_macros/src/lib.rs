@@ -0,0 +1,145 @@
+//! The `#[lox_native]` attribute, which turns a Rust function into a native Lox function.
+
+use proc_macro2::TokenStream;
+use quote::format_ident;
+use quote::quote;
+
+/// Generates a `register_<function>` function defining the annotated function as a global of an
+/// interpreter, under the given name.
+///
+/// The arity is the number of parameters. Each parameter is converted from its Lox value with
+/// `TryFrom<LoxObject>`, unless it is a `LoxObject` already. The return value is converted with
+/// `LoxObject::from`, and a returned `Result` with a `String` error fails the call.
+///
+/// ```ignore
+/// #[lox_native("half")]
+/// fn half(n: f64) -> f64 {
+///     n / 2.0
+/// }
+///
+/// register_half(&mut interpreter);
+/// ```
+#[proc_macro_attribute]
+pub fn lox_native(
+    attr: proc_macro::TokenStream,
+    item: proc_macro::TokenStream,
+) -> proc_macro::TokenStream {
+    let expanded = syn::parse(attr)
+        .and_then(|name| Ok((name, syn::parse(item)?)))
+        .and_then(|(name, function)| expand(&name, &function));
+    match expanded {
+        Ok(expanded) => expanded.into(),
+        Err(error) => error.to_compile_error().into(),
+    }
+}
+
+fn expand(name: &syn::LitStr, function: &syn::ItemFn) -> syn::Result<TokenStream> {
+    let ident = &function.sig.ident;
+    let register = format_ident!("register_{}", ident);
+    let visibility = &function.vis;
+
+    let mut parameters = vec![];
+    let mut conversions = vec![];
+    for (index, input) in function.sig.inputs.iter().enumerate() {
+        let syn::FnArg::Typed(input) = input else {
+            return Err(syn::Error::new_spanned(
+                input,
+                "native functions can't take self",
+            ));
+        };
+        let parameter = format_ident!("argument_{}", index);
+        let ty = &input.ty;
+        conversions.push(if is_lox_object(ty) {
+            quote! { let #parameter = arguments.next().unwrap(); }
+        } else {
+            quote! {
+                let #parameter: #ty = ::std::convert::TryFrom::try_from(arguments.next().unwrap())
+                    .map_err(|error: ::lox::interpreter::EvaluateError| {
+                        format!("{}(): {}", #name, error.0)
+                    })?;
+            }
+        });
+        parameters.push(parameter);
+    }
+    let arity = parameters.len();
+
+    let call = quote! { #ident(#(#parameters),*) };
+    let result = match &function.sig.output {
+        syn::ReturnType::Default => quote! { #call; Ok(::lox::object::LoxObject::Nil) },
+        syn::ReturnType::Type(_, ty) if is_result(ty) => {
+            quote! { #call.map(::lox::object::LoxObject::from) }
+        }
+        syn::ReturnType::Type(..) => quote! { Ok(::lox::object::LoxObject::from(#call)) },
+    };
+
+    Ok(quote! {
+        #function
+
+        #visibility fn #register(interpreter: &mut ::lox::interpreter::Interpreter) {
+            interpreter.define_native_fn(
+                #name,
+                #arity,
+                |arguments: Vec<::lox::object::LoxObject>| {
+                    let mut arguments = arguments.into_iter();
+                    #(#conversions)*
+                    #result
+                },
+            )
+        }
+    })
+}
+
+/// Whether `ty` is named `last`, like `LoxObject` or `lox::object::LoxObject`.
+fn is_named(ty: &syn::Type, last: &str) -> bool {
+    match ty {
+        syn::Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == last),
+        _ => false,
+    }
+}
+
+fn is_lox_object(ty: &syn::Type) -> bool {
+    is_named(ty, "LoxObject")
+}
+
+fn is_result(ty: &syn::Type) -> bool {
+    is_named(ty, "Result")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn expand_str(name: &str, function: &str) -> String {
+        let name = syn::LitStr::new(name, proc_macro2::Span::call_site());
+        expand(&name, &syn::parse_str(function).unwrap())
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn generates_a_register_function() {
+        let expanded = expand_str("half", "pub fn half(n: f64) -> f64 { n / 2.0 }");
+        assert!(expanded.contains("pub fn register_half"));
+        assert!(expanded.contains("define_native_fn (\"half\" , 1usize"));
+    }
+
+    #[test]
+    fn arity_is_the_number_of_parameters() {
+        let expanded = expand_str("nothing", "fn nothing() {}");
+        assert!(expanded.contains("define_native_fn (\"nothing\" , 0usize"));
+        let expanded = expand_str("join", "fn join(a: String, b: String, c: String) {}");
+        assert!(expanded.contains("define_native_fn (\"join\" , 3usize"));
+    }
+
+    #[test]
+    fn methods_are_rejected() {
+        let name = syn::LitStr::new("len", proc_macro2::Span::call_site());
+        let function = syn::parse_str("fn len(&self) -> f64 { 0.0 }").unwrap();
+        let error = expand(&name, &function).unwrap_err();
+        assert_eq!(error.to_string(), "native functions can't take self");
+    }
+}
@@ -1,4 +1,19 @@
-use std::usize;
+//! An iterator adapter that can look several items ahead.
+//!
+//! The crate only depends on `core`. The `std` feature (enabled by default) is where anything
+//! needing the standard library goes.
+
+#![no_std]
+
+#[cfg(any(test, feature = "std"))]
+extern crate std;
+
+use core::fmt;
+use core::iter::FusedIterator;
+use core::iter::Map;
+
+/// Alias kept for code written against the `prepeek` crate, which this crate replaces.
+pub type Prepeek<I, const N: usize> = Prefetched<I, N>;
 
 pub struct Prefetched<I: Iterator, const L: usize> {
     iter: I,
@@ -20,6 +35,30 @@ impl<I: Iterator, const L: usize> Prefetched<I, L> {
         s
     }
 
+    /// Like [`Prefetched::new`], but applies `f` to each item as it is pulled into the ring buffer,
+    /// so peeked items are already transformed.
+    pub fn with_transform<F>(iter: I, f: F) -> Prefetched<Map<I, F>, L>
+    where
+        F: FnMut(I::Item) -> I::Item,
+    {
+        Prefetched::new(iter.map(f))
+    }
+
+    /// Builds a `Prefetched` from an already filled ring buffer, without pulling from `iter`.
+    ///
+    /// `ring_index` is the position of the next item in `ring` and must be smaller than `L` (or
+    /// zero when `L` is zero). The ring is read in order starting from `ring_index` and wrapping
+    /// around, so the slots before `ring_index` hold the items that come last, not consumed ones.
+    /// Any `None` in the ring must come after all of its `Some` items in that order.
+    pub fn from_parts(ring: [Option<I::Item>; L], ring_index: usize, iter: I) -> Self {
+        debug_assert!(ring_index < L || (L == 0 && ring_index == 0));
+        Self {
+            iter,
+            ring,
+            ring_index,
+        }
+    }
+
     pub fn peek(&self) -> Option<&I::Item> {
         self.peek_nth(0)
     }
@@ -31,6 +70,81 @@ impl<I: Iterator, const L: usize> Prefetched<I, L> {
             self.ring[(self.ring_index + n) % L].as_ref()
         }
     }
+
+    /// Applies `f` to the `n`th buffered item, without advancing.
+    pub fn peek_map<T, F: Fn(&I::Item) -> T>(&self, n: usize, f: F) -> Option<T> {
+        self.peek_nth(n).map(f)
+    }
+
+    /// Replaces the next item with `new_item`, returning the item it replaces.
+    ///
+    /// If the buffer is empty, `new_item` becomes the next (and only) item and `None` is returned.
+    /// When `L` is zero nothing can be buffered, so `new_item` is dropped.
+    pub fn replace_peek(&mut self, new_item: I::Item) -> Option<I::Item> {
+        if L == 0 {
+            return None;
+        }
+        self.ring[self.ring_index].replace(new_item)
+    }
+
+    /// Iterates over all the items currently buffered, starting with the next one.
+    pub fn peek_all(&self) -> impl Iterator<Item = &I::Item> + '_ {
+        (0..L).filter_map(|n| self.peek_nth(n))
+    }
+
+    /// Skips over the next `n` items, returning how many were actually skipped.
+    ///
+    /// The result is smaller than `n` only if the iterator ran out of items.
+    pub fn advance_by_n(&mut self, n: usize) -> usize {
+        if n < L {
+            // only part of the ring is skipped, shift it as usual
+            return (0..n).take_while(|_| self.next().is_some()).count();
+        }
+        // the whole ring is skipped, so drain it and skip the rest directly on the inner iterator
+        let buffered = self.ring.iter_mut().filter_map(Option::take).count();
+        let skipped = buffered + self.iter.by_ref().take(n - buffered).count();
+        self.ring_index = 0;
+        for item in self.ring.iter_mut() {
+            *item = self.iter.next();
+        }
+        skipped
+    }
+
+    /// Takes items for as long as `f` holds for the next one, which is left in place when it fails.
+    ///
+    /// Items keep being pulled into the ring buffer, so this can go past the items buffered when
+    /// it is called. Nothing is taken when `L` is zero, since nothing can be peeked.
+    #[cfg(feature = "std")]
+    pub fn take_while_peek<F: Fn(&I::Item) -> bool>(&mut self, f: F) -> std::vec::Vec<I::Item> {
+        let mut taken = std::vec::Vec::new();
+        while self.peek().is_some_and(&f) {
+            taken.extend(self.next());
+        }
+        taken
+    }
+
+    /// Returns the wrapped iterator, discarding the ring buffer.
+    ///
+    /// Items still in the ring buffer are lost: they have already been pulled from the inner
+    /// iterator, so it does not yield them again.
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+
+    /// The wrapped iterator, which is already advanced past the buffered items.
+    pub fn inner(&self) -> &I {
+        &self.iter
+    }
+
+    /// Mutable access to the wrapped iterator. Items taken from it directly skip the ring buffer.
+    pub fn inner_mut(&mut self) -> &mut I {
+        &mut self.iter
+    }
+
+    /// Number of items currently held in the ring buffer.
+    fn buffered(&self) -> usize {
+        self.ring.iter().filter(|item| item.is_some()).count()
+    }
 }
 
 impl<I: Iterator, const L: usize> Iterator for Prefetched<I, L> {
@@ -39,16 +153,66 @@ impl<I: Iterator, const L: usize> Iterator for Prefetched<I, L> {
     fn next(&mut self) -> Option<Self::Item> {
         let mut v = self.iter.next();
         if L != 0 {
-            v = std::mem::replace(&mut self.ring[self.ring_index], v);
+            v = core::mem::replace(&mut self.ring[self.ring_index], v);
             self.ring_index = (self.ring_index + 1) % L;
         }
         v
     }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let buffered = self.buffered();
+        let (lower, upper) = self.iter.size_hint();
+        (
+            lower.saturating_add(buffered),
+            upper.and_then(|upper| upper.checked_add(buffered)),
+        )
+    }
+}
+
+impl<I: DoubleEndedIterator, const L: usize> DoubleEndedIterator for Prefetched<I, L> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        // the ring holds the items at the front, so the back comes from the inner iterator until
+        // it runs dry, after which the ring is drained from its last item
+        self.iter.next_back().or_else(|| {
+            (0..L)
+                .rev()
+                .find_map(|n| self.ring[(self.ring_index + n) % L].take())
+        })
+    }
+}
+
+impl<I: ExactSizeIterator, const L: usize> ExactSizeIterator for Prefetched<I, L> {
+    fn len(&self) -> usize {
+        self.iter.len() + self.buffered()
+    }
+}
+
+// Once the inner iterator is exhausted, `next` only drains the ring, so fusing relies on the inner
+// iterator staying exhausted as well.
+impl<I: FusedIterator, const L: usize> FusedIterator for Prefetched<I, L> {}
+
+impl<I: Iterator, const L: usize> fmt::Debug for Prefetched<I, L>
+where
+    I::Item: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // show the ring in logical order, starting from the current position
+        write!(f, "Prefetched(")?;
+        f.debug_list()
+            .entries((0..L).map(|n| &self.ring[(self.ring_index + n) % L]))
+            .finish()?;
+        write!(f, ")")
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::format;
+    use std::string::String;
+    use std::string::ToString;
+    use std::vec;
+    use std::vec::Vec;
 
     #[test]
     fn test_simple() {
@@ -93,4 +257,272 @@ mod tests {
         assert_eq!(peekable.peek_nth(1).cloned(), None);
         assert_eq!(peekable.peek_nth(2).cloned(), None);
     }
+
+    #[test]
+    fn test_debug() {
+        let array = [1, 2];
+        let mut peekable = Prefetched::<std::array::IntoIter<i32, 2>, 3>::new(array.into_iter());
+        assert_eq!(
+            format!("{:?}", peekable),
+            "Prefetched([Some(1), Some(2), None])"
+        );
+        peekable.next();
+        assert_eq!(
+            format!("{:?}", peekable),
+            "Prefetched([Some(2), None, None])"
+        );
+    }
+
+    #[test]
+    fn test_fused() {
+        let slice = [1, 2, 3];
+        let mut peekable = Prefetched::<_, 2>::new(slice.iter());
+        assert_eq!(peekable.next(), Some(&1));
+        assert_eq!(peekable.next(), Some(&2));
+        assert_eq!(peekable.next(), Some(&3));
+        for _ in 3..10 {
+            assert_eq!(peekable.next(), None);
+        }
+    }
+
+    #[test]
+    fn test_size_hint() {
+        let array = [1, 2, 3];
+        let mut peekable = Prefetched::<_, 2>::new(array.into_iter());
+        assert_eq!(peekable.size_hint(), (3, Some(3)));
+        peekable.next();
+        assert_eq!(peekable.size_hint(), (2, Some(2)));
+        peekable.next();
+        assert_eq!(peekable.size_hint(), (1, Some(1)));
+        peekable.next();
+        assert_eq!(peekable.size_hint(), (0, Some(0)));
+        peekable.next();
+        assert_eq!(peekable.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn test_with_transform() {
+        let words = ["let", "x", "in"].map(String::from);
+        let mut peekable =
+            Prefetched::<_, 2>::with_transform(words.iter().cloned(), |word: String| {
+                word.to_ascii_uppercase()
+            });
+        assert_eq!(peekable.peek().map(String::as_str), Some("LET"));
+        assert_eq!(peekable.peek_nth(1).map(String::as_str), Some("X"));
+        assert_eq!(peekable.next(), Some("LET".to_string()));
+        assert_eq!(peekable.collect::<Vec<_>>(), vec!["X", "IN"]);
+        assert_eq!(words, ["let", "x", "in"]);
+    }
+
+    #[test]
+    fn test_inner() {
+        let array = [1, 2, 3, 4, 5];
+        let mut peekable = Prefetched::<_, 2>::new(array.into_iter());
+        assert_eq!(peekable.inner().len(), 3);
+        assert_eq!(peekable.inner_mut().next(), Some(3));
+        assert_eq!(peekable.next(), Some(1));
+
+        let mut inner = peekable.into_inner();
+        assert_eq!(inner.next(), Some(5));
+        assert_eq!(inner.next(), None);
+    }
+
+    #[test]
+    fn test_into_inner_fully_buffered() {
+        let array = [1, 2, 3];
+        let peekable = Prefetched::<_, 5>::new(array.into_iter());
+        assert_eq!(peekable.into_inner().next(), None);
+    }
+
+    #[test]
+    fn test_len() {
+        let array = [1, 2, 3, 4];
+        let mut peekable = Prefetched::<_, 2>::new(array.into_iter());
+        for len in (0..=4).rev() {
+            assert_eq!(peekable.len(), len);
+            peekable.next();
+        }
+        assert_eq!(peekable.len(), 0);
+
+        // the ring is larger than the source
+        let peekable = Prefetched::<_, 5>::new([1, 2].into_iter());
+        assert_eq!(peekable.len(), 2);
+    }
+
+    #[test]
+    fn test_collect_capacity() {
+        let array = [0; 10];
+        let peekable = Prefetched::<_, 2>::new(array.into_iter());
+        let collected = Vec::from_iter(peekable);
+        assert_eq!(collected.len(), 10);
+        assert_eq!(collected.capacity(), 10);
+
+        let peekable = Prefetched::<_, 10>::new([0; 7].into_iter());
+        assert_eq!(Vec::from_iter(peekable).capacity(), 7);
+    }
+
+    #[test]
+    fn test_advance_by_n() {
+        let array = [1, 2, 3, 4, 5, 6, 7, 8];
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.advance_by_n(0), 0);
+        assert_eq!(peekable.peek().cloned(), Some(1));
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.advance_by_n(2), 2);
+        assert_eq!(peekable.peek().cloned(), Some(3));
+        assert_eq!(peekable.peek_nth(2).cloned(), Some(5));
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.advance_by_n(3), 3);
+        assert_eq!(peekable.peek().cloned(), Some(4));
+        assert_eq!(peekable.peek_nth(2).cloned(), Some(6));
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.advance_by_n(5), 5);
+        assert_eq!(peekable.peek().cloned(), Some(6));
+        assert_eq!(peekable.peek_nth(2).cloned(), Some(8));
+        assert_eq!(peekable.next(), Some(6));
+    }
+
+    #[test]
+    fn test_advance_by_n_past_end() {
+        let array = [1, 2, 3, 4];
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.advance_by_n(10), 4);
+        assert_eq!(peekable.peek().cloned(), None);
+        assert_eq!(peekable.next(), None);
+
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        peekable.advance_by_n(3);
+        assert_eq!(peekable.advance_by_n(2), 1);
+        assert_eq!(peekable.next(), None);
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn test_take_while_peek() {
+        let array = [1, 2, 3, 10, 4];
+
+        let mut peekable = Prefetched::<_, 2>::new(array.into_iter());
+        assert_eq!(peekable.take_while_peek(|n| *n > 5), vec![]);
+        assert_eq!(peekable.peek().cloned(), Some(1));
+
+        assert_eq!(peekable.take_while_peek(|n| *n < 5), vec![1, 2, 3]);
+        assert_eq!(peekable.peek().cloned(), Some(10));
+        assert_eq!(peekable.peek_nth(1).cloned(), Some(4));
+
+        assert_eq!(peekable.take_while_peek(|_| true), vec![10, 4]);
+        assert_eq!(peekable.peek().cloned(), None);
+        assert_eq!(peekable.next(), None);
+    }
+
+    #[test]
+    fn test_peek_map() {
+        let mut peekable = Prefetched::<_, 2>::new([1, 2, 3].into_iter());
+        assert_eq!(peekable.peek_map(0, |n| n * 10), Some(10));
+        assert_eq!(
+            peekable.peek_map(1, |n| n.to_string()),
+            Some("2".to_string())
+        );
+        assert_eq!(peekable.peek_map(2, |n| n * 10), None);
+        assert_eq!(peekable.next(), Some(1));
+
+        let empty = Prefetched::<_, 2>::new(core::iter::empty::<i32>());
+        assert_eq!(empty.peek_map(0, |n| n * 10), None);
+    }
+
+    #[test]
+    fn test_replace_peek() {
+        let mut peekable = Prefetched::<_, 2>::new([1, 2, 3].into_iter());
+        assert_eq!(peekable.next(), Some(1));
+        assert_eq!(peekable.replace_peek(20), Some(2));
+        assert_eq!(peekable.peek().cloned(), Some(20));
+        assert_eq!(peekable.peek_nth(1).cloned(), Some(3));
+        assert_eq!(peekable.next(), Some(20));
+        assert_eq!(peekable.next(), Some(3));
+
+        assert_eq!(peekable.replace_peek(4), None);
+        assert_eq!(peekable.next(), Some(4));
+        assert_eq!(peekable.next(), None);
+
+        let mut peekable = Prefetched::<_, 0>::new([1].into_iter());
+        assert_eq!(peekable.replace_peek(10), None);
+        assert_eq!(peekable.next(), Some(1));
+    }
+
+    #[test]
+    fn test_peek_all() {
+        let array = [1, 2, 3, 4];
+        let mut peekable = Prefetched::<_, 3>::new(array.into_iter());
+        assert_eq!(peekable.peek_all().collect::<Vec<_>>(), vec![&1, &2, &3]);
+
+        peekable.next();
+        assert_eq!(peekable.peek_all().collect::<Vec<_>>(), vec![&2, &3, &4]);
+
+        peekable.next();
+        assert_eq!(peekable.peek_all().collect::<Vec<_>>(), vec![&3, &4]);
+
+        peekable.next();
+        peekable.next();
+        assert_eq!(peekable.peek_all().count(), 0);
+    }
+
+    #[test]
+    fn test_double_ended() {
+        let slice = [1, 2, 3, 4, 5];
+        let mut peekable = Prefetched::<_, 2>::new(slice.iter());
+        assert_eq!(peekable.next(), Some(&1));
+        assert_eq!(peekable.next_back(), Some(&5));
+        assert_eq!(peekable.next_back(), Some(&4));
+        assert_eq!(peekable.next_back(), Some(&3));
+        assert_eq!(peekable.peek_all().collect::<Vec<_>>(), vec![&&2]);
+        assert_eq!(peekable.next(), Some(&2));
+        assert_eq!(peekable.next(), None);
+        assert_eq!(peekable.next_back(), None);
+
+        let peekable = Prefetched::<_, 3>::new(slice.iter());
+        assert_eq!(peekable.rev().collect::<Vec<_>>(), vec![&5, &4, &3, &2, &1]);
+    }
+
+    #[test]
+    fn test_double_ended_meet_in_the_middle() {
+        let slice = [1, 2, 3, 4, 5, 6];
+        let mut peekable = Prefetched::<_, 4>::new(slice.iter());
+        let mut seen = vec![];
+        while let Some(front) = peekable.next() {
+            seen.push(*front);
+            if let Some(back) = peekable.next_back() {
+                seen.push(*back);
+            }
+        }
+        seen.sort();
+        assert_eq!(seen, vec![1, 2, 3, 4, 5, 6]);
+        assert_eq!(peekable.next_back(), None);
+    }
+
+    #[test]
+    fn test_from_parts() {
+        let array = [4, 5];
+        let mut peekable =
+            Prefetched::from_parts([Some(3), Some(1), Some(2)], 1, array.into_iter());
+        assert_eq!(peekable.peek().cloned(), Some(1));
+        assert_eq!(peekable.peek_nth(1).cloned(), Some(2));
+        assert_eq!(peekable.peek_nth(2).cloned(), Some(3));
+
+        assert_eq!(peekable.next(), Some(1));
+        assert_eq!(peekable.peek_nth(2).cloned(), Some(4));
+        assert_eq!(peekable.collect::<Vec<_>>(), vec![2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_from_parts_partially_empty() {
+        let mut peekable = Prefetched::from_parts([Some(1), None, None], 0, std::iter::empty());
+        assert_eq!(peekable.peek().cloned(), Some(1));
+        assert_eq!(peekable.peek_nth(1).cloned(), None);
+        assert_eq!(peekable.next(), Some(1));
+        assert_eq!(peekable.next(), None);
+    }
 }
@@ -0,0 +1,121 @@
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::Context;
+use rustyline::Helper;
+
+use crate::scanning;
+
+/// Completes the word before the cursor in the REPL with keywords and variable names.
+#[derive(Default)]
+pub struct LoxCompleter {
+    /// Names of the variables in scope, natives included.
+    names: Vec<String>,
+}
+
+impl LoxCompleter {
+    /// Replaces the variable names to complete with, which change as the REPL runs.
+    pub fn set_names<'a>(&mut self, names: impl IntoIterator<Item = &'a str>) {
+        self.names = names.into_iter().map(String::from).collect();
+    }
+
+    /// Returns where the word ending at `pos` starts, and the sorted names starting with it.
+    fn candidates(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos]
+            .char_indices()
+            .rev()
+            .find(|(_, c)| !is_identifier_char(*c))
+            .map_or(0, |(i, c)| i + c.len_utf8());
+        let prefix = &line[start..pos];
+        if prefix.is_empty() || prefix.starts_with(|c: char| c.is_ascii_digit()) {
+            return (pos, vec![]);
+        }
+        let mut candidates: Vec<String> = scanning::KEYWORDS
+            .iter()
+            .copied()
+            .chain(self.names.iter().map(String::as_str))
+            .filter(|name| name.starts_with(prefix))
+            .map(String::from)
+            .collect();
+        candidates.sort_unstable();
+        candidates.dedup();
+        (start, candidates)
+    }
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Completer for LoxCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.candidates(line, pos))
+    }
+}
+
+impl Hinter for LoxCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for LoxCompleter {}
+
+impl Validator for LoxCompleter {}
+
+impl Helper for LoxCompleter {}
+
+#[cfg(test)]
+mod tests {
+    use rustyline::history::DefaultHistory;
+
+    use super::*;
+
+    fn complete(completer: &LoxCompleter, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let history = DefaultHistory::new();
+        completer
+            .complete(line, pos, &Context::new(&history))
+            .unwrap()
+    }
+
+    #[test]
+    fn completes_variables_and_keywords() {
+        let mut completer = LoxCompleter::default();
+        completer.set_names(["clock", "counter", "print"]);
+        let (start, candidates) = complete(&completer, "print(co", 8);
+        assert_eq!(start, 6);
+        assert_eq!(candidates, ["const", "continue", "counter"]);
+        let (start, candidates) = complete(&completer, "var x = cl", 10);
+        assert_eq!(start, 8);
+        assert_eq!(candidates, ["class", "clock"]);
+    }
+
+    #[test]
+    fn completion_is_case_sensitive() {
+        let mut completer = LoxCompleter::default();
+        completer.set_names(["Value", "value"]);
+        assert_eq!(complete(&completer, "Va", 2).1, ["Value"]);
+        assert!(complete(&completer, "WHILE", 5).1.is_empty());
+    }
+
+    #[test]
+    fn only_the_word_before_the_cursor_is_completed() {
+        let mut completer = LoxCompleter::default();
+        completer.set_names(["été", "étage"]);
+        let (start, candidates) = complete(&completer, "print(ét", "print(ét".len());
+        assert_eq!(start, 6);
+        assert_eq!(candidates, ["étage", "été"]);
+        assert_eq!(
+            complete(&completer, "wh x", 2),
+            (0, vec!["while".to_string()])
+        );
+        assert!(complete(&completer, "print(", 6).1.is_empty());
+        assert!(complete(&completer, "1", 1).1.is_empty());
+    }
+}
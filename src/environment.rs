@@ -1,9 +1,27 @@
 use std::collections::HashMap;
+use std::iter;
+use std::rc::Rc;
 
 use crate::object;
 
-type Variables = HashMap<String, object::LoxObject>;
+// Names are interned (see `intern::intern`), so comparing keys during lookups with an `Rc<str>`
+// first compares pointers.
+type Variables = HashMap<Rc<str>, Variable>;
 
+#[derive(Debug, Clone)]
+struct Variable {
+    value: object::LoxObject,
+    constant: bool,
+}
+
+/// A copy of every variable of an environment, to restore it later (e.g. to undo in the REPL).
+#[derive(Debug, Clone)]
+pub struct EnvironmentSnapshot {
+    globals: Variables,
+    locals: Vec<Variables>,
+}
+
+#[derive(Default)]
 pub struct Environment {
     globals: Variables,
     locals: Vec<Variables>,
@@ -21,37 +39,91 @@ impl Environment {
         self.locals.last_mut().unwrap_or(&mut self.globals)
     }
 
-    pub fn define(&mut self, name: String, value: object::LoxObject) {
-        self.current_scope().insert(name, value);
+    fn current_variables(&self) -> &Variables {
+        self.locals.last().unwrap_or(&self.globals)
     }
 
-    fn get_mut(&mut self, name: &str) -> Option<&mut object::LoxObject> {
-        self.locals
-            .iter_mut()
-            .rev()
-            .find_map(|variables| variables.get_mut(name))
-            .or(self.globals.get_mut(name))
+    pub fn define(&mut self, name: Rc<str>, value: object::LoxObject) {
+        let variable = Variable {
+            value,
+            constant: false,
+        };
+        self.current_scope().insert(name, variable);
     }
 
-    pub fn get(&self, name: &str) -> Option<object::LoxObject> {
+    /// Defines a variable that `assign` can't change.
+    pub fn define_constant(&mut self, name: Rc<str>, value: object::LoxObject) {
+        let variable = Variable {
+            value,
+            constant: true,
+        };
+        self.current_scope().insert(name, variable);
+    }
+
+    fn get_variable(&self, name: &Rc<str>) -> Option<&Variable> {
         self.locals
             .iter()
             .rev()
             .find_map(|variables| variables.get(name))
-            .or(self.globals.get(name))
-            .cloned()
+            .or_else(|| self.globals.get(name))
     }
 
-    pub fn assign(&mut self, name: &str, new_value: object::LoxObject) -> bool {
+    fn get_mut(&mut self, name: &Rc<str>) -> Option<&mut Variable> {
+        self.locals
+            .iter_mut()
+            .rev()
+            .find_map(|variables| variables.get_mut(name))
+            .or_else(|| self.globals.get_mut(name))
+    }
+
+    pub fn get(&self, name: &Rc<str>) -> Option<object::LoxObject> {
+        self.get_variable(name)
+            .map(|variable| variable.value.clone())
+    }
+
+    pub fn is_constant(&self, name: &Rc<str>) -> bool {
+        self.get_variable(name)
+            .is_some_and(|variable| variable.constant)
+    }
+
+    /// Whether `name` is declared in the innermost scope, not counting the scopes around it.
+    pub fn contains_in_current_scope(&self, name: &str) -> bool {
+        self.current_variables().contains_key(name)
+    }
+
+    /// Changes the value of a variable, returning whether it exists and is not a constant.
+    pub fn assign(&mut self, name: &Rc<str>, new_value: object::LoxObject) -> bool {
         match self.get_mut(name) {
-            Some(value) => {
-                *value = new_value;
+            Some(variable) if !variable.constant => {
+                variable.value = new_value;
                 true
             }
-            None => false,
+            _ => false,
         }
     }
 
+    /// Names of all the variables in scope, globals included, sorted and each listed once.
+    pub fn keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = iter::once(&self.globals)
+            .chain(&self.locals)
+            .flat_map(|variables| variables.keys().map(|name| &**name))
+            .collect();
+        keys.sort_unstable();
+        keys.dedup();
+        keys
+    }
+
+    /// Names of the variables of the innermost scope (the globals outside of any block), sorted.
+    pub fn local_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .current_variables()
+            .keys()
+            .map(|name| &**name)
+            .collect();
+        keys.sort_unstable();
+        keys
+    }
+
     pub fn new_scope(&mut self) {
         self.locals.push(HashMap::new())
     }
@@ -59,4 +131,122 @@ impl Environment {
     pub fn pop_scope(&mut self) {
         self.locals.pop();
     }
+
+    pub fn snapshot(&self) -> EnvironmentSnapshot {
+        EnvironmentSnapshot {
+            globals: self.globals.clone(),
+            locals: self.locals.clone(),
+        }
+    }
+
+    pub fn restore(&mut self, snapshot: EnvironmentSnapshot) {
+        self.globals = snapshot.globals;
+        self.locals = snapshot.locals;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::intern::intern;
+
+    #[test]
+    fn restore_brings_back_previous_values() {
+        let mut environment = Environment::new();
+        environment.define(intern("a"), object::LoxObject::Number(1.0));
+        let snapshot = environment.snapshot();
+        environment.define(intern("a"), object::LoxObject::Number(2.0));
+        environment.define(intern("b"), object::LoxObject::Nil);
+        environment.restore(snapshot);
+        assert_eq!(
+            environment.get(&intern("a")),
+            Some(object::LoxObject::Number(1.0))
+        );
+        assert_eq!(environment.get(&intern("b")), None);
+    }
+
+    #[test]
+    fn restore_brings_back_scopes() {
+        let mut environment = Environment::new();
+        environment.new_scope();
+        environment.define(intern("local"), object::LoxObject::True);
+        let snapshot = environment.snapshot();
+        environment.pop_scope();
+        environment.restore(snapshot);
+        assert_eq!(
+            environment.get(&intern("local")),
+            Some(object::LoxObject::True)
+        );
+        environment.pop_scope();
+        assert_eq!(environment.get(&intern("local")), None);
+    }
+
+    #[test]
+    fn snapshots_are_independent() {
+        let mut environment = Environment::new();
+        environment.define(intern("a"), object::LoxObject::Number(1.0));
+        let snapshot = environment.snapshot();
+        environment.assign(&intern("a"), object::LoxObject::Number(2.0));
+        environment.restore(snapshot.clone());
+        environment.assign(&intern("a"), object::LoxObject::Number(3.0));
+        environment.restore(snapshot);
+        assert_eq!(
+            environment.get(&intern("a")),
+            Some(object::LoxObject::Number(1.0))
+        );
+    }
+
+    #[test]
+    fn keys_list_every_variable_in_scope_once() {
+        let mut environment = Environment::new();
+        environment.define(intern("shadowed"), object::LoxObject::Number(1.0));
+        environment.define(intern("global"), object::LoxObject::Nil);
+        environment.new_scope();
+        environment.define(intern("shadowed"), object::LoxObject::Number(2.0));
+        environment.define(intern("outer"), object::LoxObject::Nil);
+        environment.new_scope();
+        environment.define(intern("shadowed"), object::LoxObject::Number(3.0));
+        environment.define(intern("inner"), object::LoxObject::Nil);
+
+        assert_eq!(environment.keys(), ["global", "inner", "outer", "shadowed"]);
+        assert_eq!(
+            environment.get(&intern("shadowed")),
+            Some(object::LoxObject::Number(3.0))
+        );
+        assert_eq!(environment.local_keys(), ["inner", "shadowed"]);
+
+        environment.pop_scope();
+        environment.pop_scope();
+        assert_eq!(environment.local_keys(), ["global", "shadowed"]);
+    }
+
+    #[test]
+    fn only_the_innermost_scope_is_current() {
+        let mut environment = Environment::new();
+        environment.define(intern("global"), object::LoxObject::Nil);
+        assert!(environment.contains_in_current_scope("global"));
+        environment.new_scope();
+        environment.define(intern("local"), object::LoxObject::Nil);
+        assert!(environment.contains_in_current_scope("local"));
+        assert!(!environment.contains_in_current_scope("global"));
+        assert!(!environment.contains_in_current_scope("missing"));
+    }
+
+    #[test]
+    fn constants_cannot_be_assigned() {
+        let mut environment = Environment::new();
+        environment.define_constant(intern("a"), object::LoxObject::Number(1.0));
+        assert!(environment.is_constant(&intern("a")));
+        assert!(!environment.assign(&intern("a"), object::LoxObject::Number(2.0)));
+        assert_eq!(
+            environment.get(&intern("a")),
+            Some(object::LoxObject::Number(1.0))
+        );
+
+        // a variable in an inner scope hides the constant
+        environment.new_scope();
+        environment.define(intern("a"), object::LoxObject::Nil);
+        assert!(!environment.is_constant(&intern("a")));
+        assert!(environment.assign(&intern("a"), object::LoxObject::True));
+    }
 }
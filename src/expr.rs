@@ -1,6 +1,8 @@
 use std::fmt::Display;
+use std::rc::Rc;
 
 use crate::object;
+use crate::tokens;
 
 #[derive(Clone, Copy)]
 pub enum LogicalOperator {
@@ -18,10 +20,11 @@ impl Display for LogicalOperator {
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum UnaryOperator {
     Neg,
     Bang,
+    BitNot,
 }
 
 impl Display for UnaryOperator {
@@ -29,12 +32,13 @@ impl Display for UnaryOperator {
         let s = match self {
             UnaryOperator::Neg => "-",
             UnaryOperator::Bang => "!",
+            UnaryOperator::BitNot => "~",
         };
         write!(f, "{}", s)
     }
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BinaryOperator {
     EqualEqual,
     BangEqual,
@@ -49,6 +53,15 @@ pub enum BinaryOperator {
     Sub,
     Mul,
     Div,
+
+    In,
+    NotIn,
+
+    BitAnd,
+    BitOr,
+    BitXor,
+    Shl,
+    Shr,
 }
 
 impl Display for BinaryOperator {
@@ -64,24 +77,83 @@ impl Display for BinaryOperator {
             BinaryOperator::Sub => "-",
             BinaryOperator::Mul => "*",
             BinaryOperator::Div => "/",
+            BinaryOperator::In => "in",
+            BinaryOperator::NotIn => "not in",
+            BinaryOperator::BitAnd => "&",
+            BinaryOperator::BitOr => "|",
+            BinaryOperator::BitXor => "^",
+            BinaryOperator::Shl => "<<",
+            BinaryOperator::Shr => ">>",
         };
         write!(f, "{}", s)
     }
 }
 
+impl TryFrom<&tokens::TokenType<'_>> for UnaryOperator {
+    type Error = ();
+
+    fn try_from(token: &tokens::TokenType) -> Result<Self, Self::Error> {
+        match token {
+            tokens::TokenType::Bang => Ok(UnaryOperator::Bang),
+            tokens::TokenType::Minus => Ok(UnaryOperator::Neg),
+            tokens::TokenType::Tilde => Ok(UnaryOperator::BitNot),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Converts the tokens that are binary operators on their own, so not `in` and `not in`.
+impl TryFrom<&tokens::TokenType<'_>> for BinaryOperator {
+    type Error = ();
+
+    fn try_from(token: &tokens::TokenType) -> Result<Self, Self::Error> {
+        match token {
+            tokens::TokenType::EqualEqual => Ok(BinaryOperator::EqualEqual),
+            tokens::TokenType::BangEqual => Ok(BinaryOperator::BangEqual),
+            tokens::TokenType::Less => Ok(BinaryOperator::LessThan),
+            tokens::TokenType::LessEqual => Ok(BinaryOperator::LessEqualThan),
+            tokens::TokenType::Greater => Ok(BinaryOperator::GreaterThan),
+            tokens::TokenType::GreaterEqual => Ok(BinaryOperator::GreaterEqualThan),
+            tokens::TokenType::Plus => Ok(BinaryOperator::Add),
+            tokens::TokenType::Minus => Ok(BinaryOperator::Sub),
+            tokens::TokenType::Star => Ok(BinaryOperator::Mul),
+            tokens::TokenType::Slash => Ok(BinaryOperator::Div),
+            tokens::TokenType::Ampersand => Ok(BinaryOperator::BitAnd),
+            tokens::TokenType::Pipe => Ok(BinaryOperator::BitOr),
+            tokens::TokenType::Caret => Ok(BinaryOperator::BitXor),
+            tokens::TokenType::LessLess => Ok(BinaryOperator::Shl),
+            tokens::TokenType::GreaterGreater => Ok(BinaryOperator::Shr),
+            _ => Err(()),
+        }
+    }
+}
+
 #[derive(Clone)]
 pub enum Expr {
     Literal(object::LoxObject),
     Unary(UnaryOperator, Box<Expr>),
     Binary(Box<Expr>, BinaryOperator, Box<Expr>),
     Logical(Box<Expr>, LogicalOperator, Box<Expr>),
-    Call{
+    Call {
         callee: Box<Expr>,
         arguments: Vec<Expr>,
     },
     Grouping(Box<Expr>),
-    Variable(String),
-    Assign(String, Box<Expr>),
+    Variable(Rc<str>),
+    Assign(Rc<str>, Box<Expr>),
+    /// A string with interpolated expressions, like `"hello ${name}!"`.
+    StringInterp(Vec<StringPart>),
+    /// Elements in parentheses separated by commas, like `(1, "two")` or `(1,)`.
+    Tuple(Vec<Expr>),
+    /// An element of a tuple, like `pair[0]`.
+    Index(Box<Expr>, Box<Expr>),
+}
+
+/// A piece of an interpolated string.
+#[derive(Clone)]
+pub enum StringPart {
+    Literal(String),
+    Expr(Expr),
 }
 
 impl Display for Expr {
@@ -94,7 +166,31 @@ impl Display for Expr {
             Expr::Variable(name) => write!(f, "${}", name),
             Expr::Assign(name, expr) => write!(f, "(= ${}, {})", name, expr),
             Expr::Logical(expr1, op, expr2) => write!(f, "({} {} {})", op, expr1, expr2),
-            Expr::Call { callee: _, arguments: _ } => todo!(),
+            Expr::Call { callee, arguments } => {
+                write!(f, "(call {}", callee)?;
+                for argument in arguments {
+                    write!(f, " {}", argument)?;
+                }
+                write!(f, ")")
+            }
+            Expr::StringInterp(parts) => {
+                write!(f, "(str")?;
+                for part in parts {
+                    match part {
+                        StringPart::Literal(text) => write!(f, " {:?}", text)?,
+                        StringPart::Expr(expr) => write!(f, " {}", expr)?,
+                    }
+                }
+                write!(f, ")")
+            }
+            Expr::Tuple(elements) => {
+                write!(f, "(tuple")?;
+                for element in elements {
+                    write!(f, " {}", element)?;
+                }
+                write!(f, ")")
+            }
+            Expr::Index(expr, index) => write!(f, "(index {} {})", expr, index),
         }
     }
 }
@@ -124,4 +220,58 @@ mod tests {
         );
         assert_eq!(expr.to_string(), "(+ true false)")
     }
+
+    #[test]
+    fn unary_operators_from_tokens() {
+        use tokens::TokenType;
+        assert_eq!(
+            UnaryOperator::try_from(&TokenType::Bang),
+            Ok(UnaryOperator::Bang)
+        );
+        assert_eq!(
+            UnaryOperator::try_from(&TokenType::Minus),
+            Ok(UnaryOperator::Neg)
+        );
+        assert_eq!(
+            UnaryOperator::try_from(&TokenType::Tilde),
+            Ok(UnaryOperator::BitNot)
+        );
+        for token in [TokenType::Plus, TokenType::Identifier("a"), TokenType::EOF] {
+            assert_eq!(UnaryOperator::try_from(&token), Err(()));
+        }
+    }
+
+    #[test]
+    fn binary_operators_from_tokens() {
+        use tokens::TokenType;
+        let operators = [
+            (TokenType::EqualEqual, BinaryOperator::EqualEqual),
+            (TokenType::BangEqual, BinaryOperator::BangEqual),
+            (TokenType::Less, BinaryOperator::LessThan),
+            (TokenType::LessEqual, BinaryOperator::LessEqualThan),
+            (TokenType::Greater, BinaryOperator::GreaterThan),
+            (TokenType::GreaterEqual, BinaryOperator::GreaterEqualThan),
+            (TokenType::Plus, BinaryOperator::Add),
+            (TokenType::Minus, BinaryOperator::Sub),
+            (TokenType::Star, BinaryOperator::Mul),
+            (TokenType::Slash, BinaryOperator::Div),
+            (TokenType::Ampersand, BinaryOperator::BitAnd),
+            (TokenType::Pipe, BinaryOperator::BitOr),
+            (TokenType::Caret, BinaryOperator::BitXor),
+            (TokenType::LessLess, BinaryOperator::Shl),
+            (TokenType::GreaterGreater, BinaryOperator::Shr),
+        ];
+        for (token, operator) in operators {
+            assert_eq!(BinaryOperator::try_from(&token), Ok(operator));
+        }
+        // `in` and `not in` are parsed separately, as `not` alone is not an operator
+        for token in [
+            TokenType::In,
+            TokenType::Not,
+            TokenType::Bang,
+            TokenType::Equal,
+        ] {
+            assert_eq!(BinaryOperator::try_from(&token), Err(()));
+        }
+    }
 }
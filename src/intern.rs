@@ -0,0 +1,81 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::rc::Weak;
+
+/// Size of the table under which dead entries are not swept.
+const MIN_SWEEP_SIZE: usize = 64;
+
+struct Table {
+    strings: HashMap<Box<str>, Weak<str>>,
+    /// Size the table must reach before dead entries are swept again.
+    sweep_at: usize,
+}
+
+thread_local! {
+    static INTERNED: RefCell<Table> = RefCell::new(Table {
+        strings: HashMap::new(),
+        sweep_at: MIN_SWEEP_SIZE,
+    });
+}
+
+/// Returns a shared copy of `s`, reusing the existing allocation if `s` is already interned.
+///
+/// Interned strings with the same content point to the same allocation, so comparing them is a
+/// pointer comparison. The table only keeps weak references, but a `Weak<str>` keeps the memory
+/// of the string allocated: entries whose string is no longer used are removed when the table
+/// has doubled in size since the last sweep, which frees them.
+pub fn intern(s: &str) -> Rc<str> {
+    INTERNED.with(|table| {
+        let mut table = table.borrow_mut();
+        if let Some(existing) = table.strings.get(s).and_then(Weak::upgrade) {
+            return existing;
+        }
+        let new: Rc<str> = Rc::from(s);
+        table.strings.insert(Box::from(s), Rc::downgrade(&new));
+        if table.strings.len() >= table.sweep_at {
+            table.strings.retain(|_, string| string.strong_count() > 0);
+            table.sweep_at = (table.strings.len() * 2).max(MIN_SWEEP_SIZE);
+        }
+        new
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_content_shares_allocation() {
+        let a = intern("name");
+        let b = intern(&String::from("name"));
+        assert!(Rc::ptr_eq(&a, &b));
+    }
+
+    #[test]
+    fn different_content_is_not_shared() {
+        let a = intern("a");
+        let b = intern("b");
+        assert!(!Rc::ptr_eq(&a, &b));
+        assert_eq!(&*a, "a");
+        assert_eq!(&*b, "b");
+    }
+
+    #[test]
+    fn dropped_strings_are_not_kept_alive() {
+        let weak = Rc::downgrade(&intern("temporary"));
+        assert!(weak.upgrade().is_none());
+        assert_eq!(&*intern("temporary"), "temporary");
+    }
+
+    #[test]
+    fn dead_entries_are_swept() {
+        let kept = intern("kept");
+        for i in 0..10 * MIN_SWEEP_SIZE {
+            intern(&format!("temporary{}", i));
+        }
+        let size = INTERNED.with(|table| table.borrow().strings.len());
+        assert!(size < 2 * MIN_SWEEP_SIZE, "{} entries", size);
+        assert!(Rc::ptr_eq(&kept, &intern("kept")));
+    }
+}
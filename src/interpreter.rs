@@ -1,236 +1,888 @@
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::mem;
+use std::path::Path;
+use std::path::PathBuf;
+use std::rc::Rc;
+
 use crate::environment;
 use crate::expr;
+use crate::intern;
+use crate::lox;
+use crate::natives;
 use crate::object;
 use crate::stmt;
 
 #[derive(Debug)]
-pub struct EvaluateError(pub &'static str);
+pub struct EvaluateError(pub String);
+
+/// State kept while running a program: its variables and where it reads from and prints to.
+pub struct Interpreter {
+    pub environment: environment::Environment,
+    input: Box<dyn BufRead>,
+    output: Box<dyn Write>,
+    /// File being run, which imports are relative to.
+    current_file: Option<PathBuf>,
+    /// Files being run, including modules still being imported, to detect circular imports.
+    running_files: HashSet<PathBuf>,
+    /// Globals exported by the file being run.
+    exports: Vec<Rc<str>>,
+    /// Whether the warnings of the programs run and of the modules imported are printed.
+    pub warnings: bool,
+    /// Number of statements run so far, for profiling.
+    pub statements_evaluated: u64,
+    /// Number of expressions evaluated so far, each operand counting as one, for profiling.
+    pub expressions_evaluated: u64,
+}
+
+impl Interpreter {
+    pub fn new() -> Self {
+        Self::with_output(Box::new(io::stdout()))
+    }
+
+    /// Creates an interpreter whose `print` writes to `output` instead of stdout.
+    pub fn with_output(output: Box<dyn Write>) -> Self {
+        Self::with_io(Box::new(io::BufReader::new(io::stdin())), output)
+    }
+
+    /// Creates an interpreter whose `input` reads from `input` and `print` writes to `output`.
+    pub fn with_io(input: Box<dyn BufRead>, output: Box<dyn Write>) -> Self {
+        let mut interpreter = Interpreter {
+            environment: environment::Environment::new(),
+            input,
+            output,
+            current_file: None,
+            running_files: HashSet::new(),
+            exports: vec![],
+            warnings: true,
+            statements_evaluated: 0,
+            expressions_evaluated: 0,
+        };
+        natives::define_natives(&mut interpreter.environment);
+        interpreter
+    }
+
+    /// Sets the file being run. Without one, imports are relative to the working directory.
+    pub fn set_current_file(&mut self, path: &Path) {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        self.running_files.insert(path.clone());
+        self.current_file = Some(path);
+    }
+
+    /// Runs the module at `path` in an environment of its own, then defines the globals it
+    /// exports in the current scope, prefixed with `alias` and a dot if there is one.
+    fn import(&mut self, path: &str, alias: Option<&str>) -> Result<(), Signal> {
+        let directory = match &self.current_file {
+            Some(file) => file.parent().unwrap_or(Path::new("")),
+            None => Path::new(""),
+        };
+        let module = directory
+            .join(path)
+            .canonicalize()
+            .map_err(|_| EvaluateError(format!("Could not find module '{}'.", path)))?;
+        if self.running_files.contains(&module) {
+            return Err(EvaluateError(format!("Circular import of '{}'.", path)).into());
+        }
+        let source = fs::read_to_string(&module)
+            .map_err(|_| EvaluateError(format!("Could not read module '{}'.", path)))?;
+        let statements = lox::parse(&source, path, self.warnings).map_err(|errors| {
+            let errors: Vec<String> = errors.iter().map(ToString::to_string).collect();
+            EvaluateError(format!(
+                "Could not parse module '{}':\n{}",
+                path,
+                errors.join("\n")
+            ))
+        })?;
+
+        let mut module_environment = environment::Environment::new();
+        natives::define_natives(&mut module_environment);
+        let environment = mem::replace(&mut self.environment, module_environment);
+        let exports = mem::take(&mut self.exports);
+        let current_file = self.current_file.replace(module.clone());
+        self.running_files.insert(module.clone());
+        let result = statements
+            .iter()
+            .try_for_each(|statement| statement.evaluate(self));
+        self.running_files.remove(&module);
+        self.current_file = current_file;
+        let module_exports = mem::replace(&mut self.exports, exports);
+        let module_environment = mem::replace(&mut self.environment, environment);
+        result?;
+
+        for name in module_exports {
+            if let Some(value) = module_environment.get(&name) {
+                let constant = module_environment.is_constant(&name);
+                let name = match alias {
+                    Some(alias) => intern::intern(&format!("{}.{}", alias, name)),
+                    None => name,
+                };
+                if constant {
+                    self.environment.define_constant(name, value);
+                } else {
+                    self.environment.define(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Defines a global variable, for programs run afterwards to use.
+    pub fn define_global(&mut self, name: &str, value: object::LoxObject) {
+        self.environment.define(intern::intern(name), value)
+    }
+
+    /// Defines a global function implemented in Rust, for programs run afterwards to call.
+    ///
+    /// An error returned by `function` becomes a runtime error of the program.
+    pub fn define_native_fn(
+        &mut self,
+        name: &str,
+        arity: usize,
+        function: impl Fn(Vec<object::LoxObject>) -> Result<object::LoxObject, String> + 'static,
+    ) {
+        let native = object::LoxNativeFunction::new(name, Some(arity), move |_, arguments| {
+            function(arguments).map_err(EvaluateError)
+        });
+        self.define_global(name, object::LoxObject::NativeFunction(Rc::new(native)));
+    }
+
+    pub fn input(&mut self) -> &mut dyn BufRead {
+        &mut self.input
+    }
+
+    pub fn output(&mut self) -> &mut dyn Write {
+        &mut self.output
+    }
+}
+
+impl Default for Interpreter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Interrupts the execution of statements, either because of an error or to jump elsewhere.
+#[derive(Debug)]
+pub enum Signal {
+    Error(EvaluateError),
+    /// Leaves the innermost loop, or the enclosing loop with the given label.
+    Break(Option<Rc<str>>),
+    /// Skips to the next iteration of the innermost loop, or of the enclosing loop with the given
+    /// label.
+    Continue(Option<Rc<str>>),
+    /// A value thrown by a `throw` statement, until a `try` statement catches it.
+    Throw(object::LoxObject),
+}
 
-pub trait Interpret<T> {
-    fn evaluate(&self, environment: &mut environment::Environment) -> Result<T, EvaluateError>;
+impl From<EvaluateError> for Signal {
+    fn from(error: EvaluateError) -> Self {
+        Signal::Error(error)
+    }
+}
+
+pub trait Interpret<T, E = EvaluateError> {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<T, E>;
 }
 
 impl Interpret<object::LoxObject> for expr::Expr {
-    fn evaluate(
-        &self,
-        environment: &mut environment::Environment,
-    ) -> Result<object::LoxObject, EvaluateError> {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<object::LoxObject, EvaluateError> {
+        interpreter.expressions_evaluated += 1;
         match self {
             expr::Expr::Literal(obj) => Ok(obj.clone()),
             expr::Expr::Unary(op, val) => {
-                let val = val.evaluate(environment)?;
+                let val = val.evaluate(interpreter)?;
                 match op {
                     expr::UnaryOperator::Neg => {
                         if let object::LoxObject::Number(n) = val {
                             Ok(object::LoxObject::Number(-n))
                         } else {
-                            Err(EvaluateError("cannot negate a non-number"))
+                            Err(EvaluateError(format!(
+                                "cannot negate a non-number, got {}",
+                                val.type_name()
+                            )))
+                        }
+                    }
+                    expr::UnaryOperator::Bang => Ok(object::LoxObject::from(!val.is_truthy())),
+                    expr::UnaryOperator::BitNot => {
+                        if let object::LoxObject::Number(n) = val {
+                            Ok(object::LoxObject::Number(!(n as i64) as f32))
+                        } else {
+                            Err(EvaluateError(format!(
+                                "cannot invert a non-number, got {}",
+                                val.type_name()
+                            )))
                         }
                     }
-                    expr::UnaryOperator::Bang => Ok(object::LoxObject::from(!is_truthy(&val))),
                 }
             }
             expr::Expr::Binary(expr1, op, expr2) => match op {
                 expr::BinaryOperator::EqualEqual => Ok(object::LoxObject::from(
-                    expr1.evaluate(environment)? == expr2.evaluate(environment)?,
+                    expr1.evaluate(interpreter)? == expr2.evaluate(interpreter)?,
                 )),
                 expr::BinaryOperator::BangEqual => Ok(object::LoxObject::from(
-                    expr1.evaluate(environment)? != expr2.evaluate(environment)?,
+                    expr1.evaluate(interpreter)? != expr2.evaluate(interpreter)?,
                 )),
                 expr::BinaryOperator::LessThan => {
-                    compare_numbers(expr1, expr2, environment, |n1, n2| n1 < n2)
+                    compare(expr1, expr2, interpreter, PartialOrd::lt)
                 }
                 expr::BinaryOperator::LessEqualThan => {
-                    compare_numbers(expr1, expr2, environment, |n1, n2| n1 <= n2)
+                    compare(expr1, expr2, interpreter, PartialOrd::le)
                 }
                 expr::BinaryOperator::GreaterThan => {
-                    compare_numbers(expr1, expr2, environment, |n1, n2| n1 > n2)
+                    compare(expr1, expr2, interpreter, PartialOrd::gt)
                 }
                 expr::BinaryOperator::GreaterEqualThan => {
-                    compare_numbers(expr1, expr2, environment, |n1, n2| n1 >= n2)
+                    compare(expr1, expr2, interpreter, PartialOrd::ge)
                 }
-                expr::BinaryOperator::Add => match expr1.evaluate(environment)? {
-                    object::LoxObject::Number(n1) => {
-                        if let object::LoxObject::Number(n2) = expr2.evaluate(environment)? {
-                            Ok(object::LoxObject::from(n1 + n2))
-                        } else {
-                            Err(EvaluateError(
-                                "number value cannot be added with non-number operand",
-                            ))
-                        }
-                    }
-                    object::LoxObject::String(s1) => {
-                        if let object::LoxObject::String(s2) = expr2.evaluate(environment)? {
+                expr::BinaryOperator::Add => match expr1.evaluate(interpreter)? {
+                    object::LoxObject::Number(n1) => match expr2.evaluate(interpreter)? {
+                        object::LoxObject::Number(n2) => Ok(object::LoxObject::from(n1 + n2)),
+                        other => Err(EvaluateError(format!(
+                            "number value cannot be added with non-number operand, got {}",
+                            other.type_name()
+                        ))),
+                    },
+                    object::LoxObject::String(s1) => match expr2.evaluate(interpreter)? {
+                        object::LoxObject::String(s2) => {
                             Ok(object::LoxObject::from([s1, s2].concat()))
-                        } else {
-                            Err(EvaluateError(
-                                "string value cannot be added to non-string value",
-                            ))
                         }
-                    }
-                    object::LoxObject::True | object::LoxObject::False => {
-                        Err(EvaluateError("boolean cannot be an operand to addition"))
-                    }
-                    object::LoxObject::Nil => {
-                        Err(EvaluateError("nil cannot be an operand to addition"))
-                    }
+                        other => Err(EvaluateError(format!(
+                            "string value cannot be added to non-string value, got {}",
+                            other.type_name()
+                        ))),
+                    },
+                    other => Err(EvaluateError(format!(
+                        "{} cannot be an operand to addition",
+                        other.type_name()
+                    ))),
                 },
-                expr::BinaryOperator::Sub => match expr1.evaluate(environment)? {
-                    object::LoxObject::Number(n1) => {
-                        if let object::LoxObject::Number(n2) = expr2.evaluate(environment)? {
-                            Ok(object::LoxObject::from(n1 - n2))
-                        } else {
-                            Err(EvaluateError(
-                                "number value cannot be added with non-number operand",
-                            ))
-                        }
-                    }
-                    object::LoxObject::String(_)
-                    | object::LoxObject::True
-                    | object::LoxObject::False
-                    | object::LoxObject::Nil => {
-                        Err(EvaluateError("subtraction operand cannot be non-number"))
-                    }
+                expr::BinaryOperator::Sub => match expr1.evaluate(interpreter)? {
+                    object::LoxObject::Number(n1) => match expr2.evaluate(interpreter)? {
+                        object::LoxObject::Number(n2) => Ok(object::LoxObject::from(n1 - n2)),
+                        other => Err(EvaluateError(format!(
+                            "number value cannot be subtracted by non-number operand, got {}",
+                            other.type_name()
+                        ))),
+                    },
+                    other => Err(EvaluateError(format!(
+                        "subtraction operand cannot be non-number, got {}",
+                        other.type_name()
+                    ))),
                 },
-                expr::BinaryOperator::Mul => match expr1.evaluate(environment)? {
-                    object::LoxObject::Number(n1) => {
-                        if let object::LoxObject::Number(n2) = expr2.evaluate(environment)? {
-                            Ok(object::LoxObject::from(n1 * n2))
-                        } else {
-                            Err(EvaluateError(
-                                "number value cannot be multiplied with non-number operand",
-                            ))
-                        }
-                    }
-                    object::LoxObject::String(_)
-                    | object::LoxObject::True
-                    | object::LoxObject::False
-                    | object::LoxObject::Nil => {
-                        Err(EvaluateError("multiplication operand cannot be non-number"))
-                    }
+                expr::BinaryOperator::Mul => match expr1.evaluate(interpreter)? {
+                    object::LoxObject::Number(n1) => match expr2.evaluate(interpreter)? {
+                        object::LoxObject::Number(n2) => Ok(object::LoxObject::from(n1 * n2)),
+                        other => Err(EvaluateError(format!(
+                            "number value cannot be multiplied with non-number operand, got {}",
+                            other.type_name()
+                        ))),
+                    },
+                    other => Err(EvaluateError(format!(
+                        "multiplication operand cannot be non-number, got {}",
+                        other.type_name()
+                    ))),
                 },
-                expr::BinaryOperator::Div => match expr1.evaluate(environment)? {
-                    object::LoxObject::Number(n1) => {
-                        if let object::LoxObject::Number(n2) = expr2.evaluate(environment)? {
-                            Ok(object::LoxObject::from(n1 / n2))
-                        } else {
-                            Err(EvaluateError(
-                                "number value cannot be divided by non-number operand",
-                            ))
-                        }
-                    }
-                    object::LoxObject::String(_)
-                    | object::LoxObject::True
-                    | object::LoxObject::False
-                    | object::LoxObject::Nil => {
-                        Err(EvaluateError("division operand cannot be non-number"))
-                    }
+                expr::BinaryOperator::Div => match expr1.evaluate(interpreter)? {
+                    object::LoxObject::Number(n1) => match expr2.evaluate(interpreter)? {
+                        object::LoxObject::Number(n2) => Ok(object::LoxObject::from(n1 / n2)),
+                        other => Err(EvaluateError(format!(
+                            "number value cannot be divided by non-number operand, got {}",
+                            other.type_name()
+                        ))),
+                    },
+                    other => Err(EvaluateError(format!(
+                        "division operand cannot be non-number, got {}",
+                        other.type_name()
+                    ))),
                 },
+                expr::BinaryOperator::In => {
+                    contains(expr2, expr1, interpreter).map(object::LoxObject::from)
+                }
+                expr::BinaryOperator::NotIn => contains(expr2, expr1, interpreter)
+                    .map(|is_contained| object::LoxObject::from(!is_contained)),
+                expr::BinaryOperator::BitAnd => {
+                    bitwise(expr1, expr2, interpreter, |n1, n2| Some(n1 & n2))
+                }
+                expr::BinaryOperator::BitOr => {
+                    bitwise(expr1, expr2, interpreter, |n1, n2| Some(n1 | n2))
+                }
+                expr::BinaryOperator::BitXor => {
+                    bitwise(expr1, expr2, interpreter, |n1, n2| Some(n1 ^ n2))
+                }
+                expr::BinaryOperator::Shl => bitwise(expr1, expr2, interpreter, |n1, n2| {
+                    n1.checked_shl(u32::try_from(n2).ok()?)
+                }),
+                expr::BinaryOperator::Shr => bitwise(expr1, expr2, interpreter, |n1, n2| {
+                    n1.checked_shr(u32::try_from(n2).ok()?)
+                }),
             },
-            expr::Expr::Grouping(g) => g.evaluate(environment),
-            expr::Expr::Variable(name) => environment
+            expr::Expr::Grouping(g) => g.evaluate(interpreter),
+            expr::Expr::Variable(name) => interpreter
+                .environment
                 .get(name)
-                .ok_or(EvaluateError("Undefined variable")),
+                .ok_or_else(|| EvaluateError(format!("Undefined variable '{}'.", name))),
             expr::Expr::Assign(name, expr) => {
-                let new_value = expr.evaluate(environment)?;
-                if environment.assign(name, new_value.clone()) {
+                let new_value = expr.evaluate(interpreter)?;
+                // the parser only sees the constants of the code it parses, not those of
+                // previous lines of the REPL or of imported modules
+                if interpreter.environment.is_constant(name) {
+                    return Err(EvaluateError(format!(
+                        "Can't assign to constant '{}'.",
+                        name
+                    )));
+                }
+                if interpreter.environment.assign(name, new_value.clone()) {
                     Ok(new_value)
                 } else {
-                    Err(EvaluateError("Undefined variable."))
+                    Err(EvaluateError(format!("Undefined variable '{}'.", name)))
                 }
             }
             expr::Expr::Logical(expr1, op, expr2) => {
-                let evaluated = expr1.evaluate(environment)?;
+                let evaluated = expr1.evaluate(interpreter)?;
                 match op {
                     expr::LogicalOperator::Or => {
-                        if is_truthy(&evaluated) {
+                        if evaluated.is_truthy() {
                             // first statement was true, short-circuit
                             return Ok(evaluated);
                         }
                     }
                     expr::LogicalOperator::And => {
-                        if !is_truthy(&evaluated) {
+                        if !evaluated.is_truthy() {
                             // first statement was false, short-circuit
                             return Ok(evaluated);
                         }
                     }
                 };
-                expr2.evaluate(environment)
+                expr2.evaluate(interpreter)
+            }
+            expr::Expr::StringInterp(parts) => {
+                let mut string = String::new();
+                for part in parts {
+                    match part {
+                        expr::StringPart::Literal(text) => string.push_str(text),
+                        expr::StringPart::Expr(expression) => {
+                            string.push_str(&expression.evaluate(interpreter)?.to_string())
+                        }
+                    }
+                }
+                Ok(object::LoxObject::String(string))
+            }
+            expr::Expr::Tuple(elements) => {
+                let elements = elements
+                    .iter()
+                    .map(|element| element.evaluate(interpreter))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(object::LoxObject::Tuple(Rc::new(elements)))
+            }
+            expr::Expr::Index(tuple, index) => {
+                match (tuple.evaluate(interpreter)?, index.evaluate(interpreter)?) {
+                    (object::LoxObject::Tuple(elements), object::LoxObject::Number(n)) => {
+                        if n.fract() != 0.0 || n < 0.0 || n as usize >= elements.len() {
+                            return Err(EvaluateError(format!(
+                                "tuple index {} out of range for {} elements",
+                                n,
+                                elements.len()
+                            )));
+                        }
+                        Ok(elements[n as usize].clone())
+                    }
+                    (object::LoxObject::Tuple(_), index) => Err(EvaluateError(format!(
+                        "tuple index must be a number, got {}",
+                        index.type_name()
+                    ))),
+                    (other, _) => Err(EvaluateError(format!(
+                        "only tuples can be indexed, got {}",
+                        other.type_name()
+                    ))),
+                }
+            }
+            expr::Expr::Call { callee, arguments } => {
+                let callee = callee.evaluate(interpreter)?;
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(interpreter))
+                    .collect::<Result<Vec<_>, _>>()?;
+                match callee {
+                    object::LoxObject::NativeFunction(function) => {
+                        match function.arity {
+                            Some(arity) if arity != arguments.len() => {
+                                return Err(EvaluateError(format!(
+                                    "'{}' expected {} arguments but got {}.",
+                                    function.name,
+                                    arity,
+                                    arguments.len()
+                                )));
+                            }
+                            _ => {}
+                        }
+                        (function.function)(interpreter, arguments)
+                    }
+                    other => Err(EvaluateError(format!(
+                        "Can only call functions, got {}.",
+                        other.type_name()
+                    ))),
+                }
             }
-            expr::Expr::Call { callee: _, arguments: _ } => todo!(),
         }
     }
 }
 
-fn is_truthy(val: &object::LoxObject) -> bool {
-    match val {
-        object::LoxObject::Number(n) => *n != 0.0,
-        object::LoxObject::String(s) => s != "",
-        object::LoxObject::True => true,
-        object::LoxObject::False => false,
-        object::LoxObject::Nil => false,
+/// Compares two values of the same type, which must be ordered, like numbers or strings.
+fn compare(
+    expr1: &expr::Expr,
+    expr2: &expr::Expr,
+    interpreter: &mut Interpreter,
+    compare_fn: fn(&object::LoxObject, &object::LoxObject) -> bool,
+) -> Result<object::LoxObject, EvaluateError> {
+    let obj1 = expr1.evaluate(interpreter)?;
+    let obj2 = expr2.evaluate(interpreter)?;
+    let comparable = obj1.type_name() == obj2.type_name()
+        && !matches!(
+            obj1,
+            object::LoxObject::NativeFunction(_)
+                | object::LoxObject::Nil
+                | object::LoxObject::Tuple(_)
+        );
+    if comparable {
+        Ok(object::LoxObject::from(compare_fn(&obj1, &obj2)))
+    } else {
+        Err(EvaluateError(format!(
+            "cannot compare {} with {}",
+            obj1.type_name(),
+            obj2.type_name()
+        )))
     }
 }
 
-fn compare_numbers<F>(
+/// Applies a bitwise operation to two numbers, truncated to integers.
+///
+/// The operation returns `None` if it can't be applied to the operands, like shifting by a
+/// negative amount.
+fn bitwise<F>(
     expr1: &expr::Expr,
     expr2: &expr::Expr,
-    environment: &mut environment::Environment,
-    compare_fn: F,
+    interpreter: &mut Interpreter,
+    operation: F,
 ) -> Result<object::LoxObject, EvaluateError>
 where
-    F: Fn(f32, f32) -> bool,
+    F: Fn(i64, i64) -> Option<i64>,
 {
-    match (expr1.evaluate(environment)?, expr2.evaluate(environment)?) {
+    match (expr1.evaluate(interpreter)?, expr2.evaluate(interpreter)?) {
         (object::LoxObject::Number(n1), object::LoxObject::Number(n2)) => {
-            Ok(object::LoxObject::from(compare_fn(n1, n2)))
+            match operation(n1 as i64, n2 as i64) {
+                Some(result) => Ok(object::LoxObject::Number(result as f32)),
+                None => Err(EvaluateError("shift amount out of range".to_string())),
+            }
         }
-        _ => Err(EvaluateError("comparison can only between two numbers")),
+        (obj1, obj2) => Err(EvaluateError(format!(
+            "bitwise operands must be numbers, got {} and {}",
+            obj1.type_name(),
+            obj2.type_name()
+        ))),
     }
 }
 
-impl Interpret<()> for stmt::Stmt {
-    fn evaluate(&self, environment: &mut environment::Environment) -> Result<(), EvaluateError> {
+/// Whether `element` is in `collection`, which can be a string or a tuple.
+fn contains(
+    collection: &expr::Expr,
+    element: &expr::Expr,
+    interpreter: &mut Interpreter,
+) -> Result<bool, EvaluateError> {
+    let element = element.evaluate(interpreter)?;
+    match (element, collection.evaluate(interpreter)?) {
+        (object::LoxObject::String(element), object::LoxObject::String(collection)) => {
+            Ok(collection.contains(&element))
+        }
+        (element, object::LoxObject::Tuple(elements)) => Ok(elements.contains(&element)),
+        (element, object::LoxObject::String(_)) => Err(EvaluateError(format!(
+            "only strings can be searched for in a string, got {}",
+            element.type_name()
+        ))),
+        (_, collection) => Err(EvaluateError(format!(
+            "right operand of 'in' must be a collection, got {}",
+            collection.type_name()
+        ))),
+    }
+}
+
+impl Interpret<(), Signal> for stmt::Stmt {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<(), Signal> {
+        interpreter.statements_evaluated += 1;
         match self {
             stmt::Stmt::Expression(expr1) => {
-                expr1.evaluate(environment)?;
-            }
-            stmt::Stmt::Print(expr1) => {
-                println!("{}", expr1.evaluate(environment)?);
+                expr1.evaluate(interpreter)?;
             }
             stmt::Stmt::Var { name, initializer } => {
                 let value = match initializer {
-                    Some(expr) => expr.evaluate(environment)?,
+                    Some(expr) => expr.evaluate(interpreter)?,
                     None => object::LoxObject::Nil,
                 };
-                environment.define(name.to_string(), value)
+                interpreter.environment.define(name.clone(), value)
+            }
+            stmt::Stmt::Const { name, initializer } => {
+                let value = initializer.evaluate(interpreter)?;
+                interpreter.environment.define_constant(name.clone(), value)
             }
             stmt::Stmt::Block(statements) => {
-                environment.new_scope();
-                for statement in statements {
-                    statement.evaluate(environment)?;
-                }
-                environment.pop_scope();
+                interpreter.environment.new_scope();
+                let result = statements
+                    .iter()
+                    .try_for_each(|statement| statement.evaluate(interpreter));
+                // the scope is left even when a break or an error interrupts the block
+                interpreter.environment.pop_scope();
+                result?;
             }
             stmt::Stmt::If {
                 condition,
                 then_branch,
                 else_branch,
             } => {
-                if is_truthy(&condition.evaluate(environment)?) {
-                    then_branch.evaluate(environment)?;
+                if condition.evaluate(interpreter)?.is_truthy() {
+                    then_branch.evaluate(interpreter)?;
                 } else {
                     if let Some(statement) = else_branch {
-                        statement.evaluate(environment)?;
+                        statement.evaluate(interpreter)?;
                     }
                 }
             }
             stmt::Stmt::While(condition, body) => {
-                while is_truthy(&condition.evaluate(environment)?) {
-                    body.evaluate(environment)?;
+                while condition.evaluate(interpreter)?.is_truthy() {
+                    if !keep_looping(body.evaluate(interpreter), None)? {
+                        break;
+                    }
                 }
-            },
+            }
+            stmt::Stmt::For {
+                label,
+                condition,
+                increment,
+                body,
+            } => {
+                while condition.evaluate(interpreter)?.is_truthy() {
+                    if !keep_looping(body.evaluate(interpreter), label.as_ref())? {
+                        break;
+                    }
+                    if let Some(increment) = increment {
+                        increment.evaluate(interpreter)?;
+                    }
+                }
+            }
+            stmt::Stmt::Loop { body } => while keep_looping(body.evaluate(interpreter), None)? {},
+            stmt::Stmt::Repeat { count, body } => {
+                let count = match count.evaluate(interpreter)? {
+                    object::LoxObject::Number(n) if n >= 0.0 && n.fract() == 0.0 => n as usize,
+                    _ => {
+                        return Err(EvaluateError(
+                            "repeat count must be a non-negative integer".to_string(),
+                        )
+                        .into())
+                    }
+                };
+                for _ in 0..count {
+                    if !keep_looping(body.evaluate(interpreter), None)? {
+                        break;
+                    }
+                }
+            }
+            stmt::Stmt::Break { label } => return Err(Signal::Break(label.clone())),
+            stmt::Stmt::Continue { label } => return Err(Signal::Continue(label.clone())),
+            stmt::Stmt::TryCatch {
+                body,
+                catch_var,
+                catch_body,
+                finally_body,
+            } => {
+                let result = match body.evaluate(interpreter) {
+                    Err(Signal::Throw(value)) => {
+                        interpreter.environment.new_scope();
+                        interpreter.environment.define(catch_var.clone(), value);
+                        let result = catch_body.evaluate(interpreter);
+                        interpreter.environment.pop_scope();
+                        result
+                    }
+                    result => result,
+                };
+                // the finally block runs however the rest ended, and takes over if it is
+                // interrupted itself
+                if let Some(finally_body) = finally_body {
+                    finally_body.evaluate(interpreter)?;
+                }
+                result?;
+            }
+            stmt::Stmt::Throw(value) => return Err(Signal::Throw(value.evaluate(interpreter)?)),
+            stmt::Stmt::Assert {
+                condition,
+                message,
+                source,
+            } => {
+                if !condition.evaluate(interpreter)?.is_truthy() {
+                    let message = match message {
+                        Some(message) => message.evaluate(interpreter)?.to_string(),
+                        None => source.clone(),
+                    };
+                    return Err(EvaluateError(format!("Assertion failed: {}", message)).into());
+                }
+            }
+            stmt::Stmt::Import { path, alias } => interpreter.import(path, alias.as_deref())?,
+            stmt::Stmt::Export(declaration) => {
+                declaration.evaluate(interpreter)?;
+                match declaration.as_ref() {
+                    stmt::Stmt::Var { name, .. } | stmt::Stmt::Const { name, .. } => {
+                        interpreter.exports.push(name.clone())
+                    }
+                    _ => {}
+                }
+            }
         }
         Ok(())
     }
 }
+
+/// Runs the statements of a program at the top level, where nothing can catch a thrown value.
+fn execute_top_level(
+    statement: &stmt::Stmt,
+    interpreter: &mut Interpreter,
+) -> Result<(), EvaluateError> {
+    match statement.evaluate(interpreter) {
+        Ok(()) => Ok(()),
+        Err(Signal::Error(error)) => Err(error),
+        Err(Signal::Throw(value)) => Err(EvaluateError(format!("Uncaught exception: {}", value))),
+        // the parser only accepts break and continue statements inside loops
+        Err(Signal::Break(_) | Signal::Continue(_)) => unreachable!(),
+    }
+}
+
+/// Handles how one run of a loop's body ended, telling whether the loop goes on.
+///
+/// Signals for other loops (or that aren't about loops at all) are passed on.
+fn keep_looping(result: Result<(), Signal>, label: Option<&Rc<str>>) -> Result<bool, Signal> {
+    match result {
+        Ok(()) | Err(Signal::Continue(None)) => Ok(true),
+        Err(Signal::Break(None)) => Ok(false),
+        Err(Signal::Continue(Some(target))) if Some(&target) == label => Ok(true),
+        Err(Signal::Break(Some(target))) if Some(&target) == label => Ok(false),
+        Err(signal) => Err(signal),
+    }
+}
+
+/// Runs a whole program, stopping at the first error.
+impl Interpret<()> for Vec<stmt::Stmt> {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<(), EvaluateError> {
+        self.iter()
+            .try_for_each(|statement| execute_top_level(statement, interpreter))
+    }
+}
+
+/// A whole program, which can either stop at its first error or report them all.
+pub struct Program {
+    pub statements: Vec<stmt::Stmt>,
+    /// Whether to go on with the next statement when one fails, like the REPL and scripts do.
+    pub accumulate_errors: bool,
+}
+
+impl Interpret<(), Vec<EvaluateError>> for Program {
+    fn evaluate(&self, interpreter: &mut Interpreter) -> Result<(), Vec<EvaluateError>> {
+        if !self.accumulate_errors {
+            return self
+                .statements
+                .evaluate(interpreter)
+                .map_err(|error| vec![error]);
+        }
+        let errors: Vec<EvaluateError> = self
+            .statements
+            .iter()
+            .filter_map(|statement| execute_top_level(statement, interpreter).err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+}
+
+/// Runs a whole program, going on after a statement fails and returning all the errors.
+pub fn execute_program(
+    statements: Vec<stmt::Stmt>,
+    interpreter: &mut Interpreter,
+) -> Result<(), Vec<EvaluateError>> {
+    Program {
+        statements,
+        accumulate_errors: true,
+    }
+    .evaluate(interpreter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn parse(source: &str) -> Vec<stmt::Stmt> {
+        Parser::from_source(source).parse().0
+    }
+
+    fn messages(errors: Vec<EvaluateError>) -> Vec<String> {
+        errors.into_iter().map(|error| error.0).collect()
+    }
+
+    const FAILING_TWICE: &str = "var a = 1; a = -\"x\"; a = 2; throw a; a = 3;";
+
+    #[test]
+    fn programs_stop_at_the_first_error() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        let error = parse(FAILING_TWICE).evaluate(&mut interpreter).unwrap_err();
+        assert_eq!(error.0, "cannot negate a non-number, got string");
+        assert_eq!(
+            interpreter.environment.get(&intern::intern("a")),
+            Some(object::LoxObject::Number(1.0))
+        );
+
+        let program = Program {
+            statements: parse(FAILING_TWICE),
+            accumulate_errors: false,
+        };
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        let errors = program.evaluate(&mut interpreter).unwrap_err();
+        assert_eq!(messages(errors), ["cannot negate a non-number, got string"]);
+    }
+
+    #[test]
+    fn programs_can_accumulate_errors() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        let errors = execute_program(parse(FAILING_TWICE), &mut interpreter).unwrap_err();
+        assert_eq!(
+            messages(errors),
+            [
+                "cannot negate a non-number, got string",
+                "Uncaught exception: 2"
+            ]
+        );
+        assert_eq!(
+            interpreter.environment.get(&intern::intern("a")),
+            Some(object::LoxObject::Number(3.0))
+        );
+        assert!(execute_program(parse("var b = 1;"), &mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn type_errors_name_the_type() {
+        let error = |source: &str| {
+            let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+            parse(source).evaluate(&mut interpreter).unwrap_err().0
+        };
+        assert_eq!(
+            error("1 + nil;"),
+            "number value cannot be added with non-number operand, got nil"
+        );
+        assert_eq!(
+            error("true + 1;"),
+            "boolean cannot be an operand to addition"
+        );
+        assert_eq!(
+            error("\"a\" * 2;"),
+            "multiplication operand cannot be non-number, got string"
+        );
+        assert_eq!(error("1 < \"2\";"), "cannot compare number with string");
+        assert_eq!(error("1();"), "Can only call functions, got number.");
+    }
+
+    fn evaluate(expression: &str) -> Result<object::LoxObject, String> {
+        let expression = Parser::from_source(expression)
+            .parse_expression_only()
+            .unwrap();
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        expression
+            .evaluate(&mut interpreter)
+            .map_err(|error| error.0)
+    }
+
+    #[test]
+    fn strings_and_booleans_can_be_compared() {
+        assert_eq!(evaluate("\"abc\" < \"abd\""), Ok(object::LoxObject::True));
+        assert_eq!(evaluate("\"b\" >= \"abc\""), Ok(object::LoxObject::True));
+        assert_eq!(evaluate("false < true"), Ok(object::LoxObject::True));
+        assert_eq!(evaluate("true <= false"), Ok(object::LoxObject::False));
+    }
+
+    #[test]
+    fn evaluations_are_counted() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        let program = parse("var a = 1; a = 2; { a; } if (a > 1) a = 3; a;");
+        program.evaluate(&mut interpreter).unwrap();
+        assert!(interpreter.statements_evaluated >= 5);
+
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        parse("1 + 2;").evaluate(&mut interpreter).unwrap();
+        assert_eq!(interpreter.statements_evaluated, 1);
+        assert_eq!(interpreter.expressions_evaluated, 3);
+    }
+
+    #[test]
+    fn calls_dispatch_to_native_functions() {
+        assert_eq!(
+            evaluate("str_find(\"abc\", \"c\")"),
+            Ok(object::LoxObject::Number(2.0))
+        );
+        assert_eq!(
+            evaluate("str_len()"),
+            Err("'str_len' expected 1 arguments but got 0.".to_string())
+        );
+        assert_eq!(
+            evaluate("\"str_len\"(\"abc\")"),
+            Err("Can only call functions, got string.".to_string())
+        );
+    }
+
+    #[test]
+    fn nil_cannot_be_compared() {
+        assert_eq!(
+            evaluate("nil < 1"),
+            Err("cannot compare nil with number".to_string())
+        );
+        assert_eq!(
+            evaluate("nil <= nil"),
+            Err("cannot compare nil with nil".to_string())
+        );
+    }
+
+    #[test]
+    fn assertions_fail_with_their_message_or_condition() {
+        let error = |source: &str| {
+            let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+            parse(source).evaluate(&mut interpreter).unwrap_err().0
+        };
+        assert_eq!(
+            error("assert nil, \"should not be \" + \"nil\";"),
+            "Assertion failed: should not be nil"
+        );
+        assert_eq!(
+            error("var x = 0; assert x > 0 and str_len(to_string(x)) != 1;"),
+            "Assertion failed: x > 0 and str_len(to_string(x)) != 1"
+        );
+        assert_eq!(error("assert !(1 < 2);"), "Assertion failed: !(1 < 2)");
+        // the condition is quoted as written, strings and spacing included
+        assert_eq!(
+            error("assert  str_len( \"ab\" )==3 ;"),
+            "Assertion failed: str_len( \"ab\" )==3"
+        );
+    }
+
+    #[test]
+    fn passing_assertions_do_not_evaluate_the_message() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        let statements = parse("assert 1 < 2; assert true, -\"not a number\";");
+        assert!(statements.evaluate(&mut interpreter).is_ok());
+    }
+
+    #[test]
+    fn constants_of_previous_programs_cannot_be_assigned() {
+        let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+        parse("const a = 1;").evaluate(&mut interpreter).unwrap();
+        let error = parse("a = 2;").evaluate(&mut interpreter).unwrap_err();
+        assert_eq!(error.0, "Can't assign to constant 'a'.");
+    }
+}
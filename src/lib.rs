@@ -0,0 +1,16 @@
+pub mod completion;
+pub mod environment;
+pub mod expr;
+pub mod intern;
+pub mod interpreter;
+pub mod lox;
+pub mod natives;
+pub mod object;
+pub mod optimizer;
+pub mod output;
+pub mod parser;
+pub mod scanning;
+pub mod stmt;
+pub mod tokens;
+
+pub use lox_native_macros::lox_native;
@@ -1,68 +1,677 @@
+use std::cell::Cell;
+use std::error;
+use std::fmt;
 use std::fs;
 use std::io;
-use std::io::Write;
+use std::io::Read;
+use std::path::Path;
+use std::rc::Rc;
 use std::sync::atomic::AtomicBool;
 use std::sync::atomic::Ordering;
 
-use crate::environment;
+use rustyline::error::ReadlineError;
+
+use crate::completion;
 use crate::interpreter;
 use crate::interpreter::Interpret;
+use crate::object;
+use crate::optimizer;
+use crate::output;
+use crate::parser;
 use crate::parser::Parser;
+use crate::scanning;
 use crate::scanning::Scanner;
-use crate::tokens;
+use crate::stmt;
 
 static HAD_ERROR: AtomicBool = AtomicBool::new(false);
 
-pub fn run_file(filepath: &str) -> io::Result<()> {
-    Ok(run(
-        &fs::read_to_string(filepath)?,
-        &mut environment::Environment::new(),
-    ))
-}
-
-pub fn run_prompt() -> io::Result<()> {
-    let mut environment = environment::Environment::new();
-    let mut stdin = io::stdin().lines();
-    while let Some(line) = {
-        print!("> ");
-        io::stdout().flush()?;
-        stdin.next()
-    } {
-        run(&line?, &mut environment);
+/// Name given to the errors of lines typed in the REPL.
+const REPL_SOURCE: &str = "<repl>";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LoxErrorKind {
+    Scan,
+    Parse,
+    Runtime,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoxError {
+    pub kind: LoxErrorKind,
+    /// Name of the source the error is in, like `foo.lox` or `<repl>`. Empty if unknown.
+    pub source: String,
+    pub line: usize,
+    pub column: Option<usize>,
+    /// Where in the source the error is, like `'foo'` or `end`. Empty if unknown.
+    pub at: String,
+    pub message: String,
+}
+
+impl fmt::Display for LoxError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.column {
+            Some(column) => write!(f, "[{}, col {}]", location(&self.source, self.line), column)?,
+            None => write!(f, "[{}]", location(&self.source, self.line))?,
+        }
+        write!(f, " Error at {}: {}", self.at, self.message)
+    }
+}
+
+impl From<scanning::ScanError> for LoxError {
+    fn from(error: scanning::ScanError) -> Self {
+        LoxError {
+            kind: LoxErrorKind::Scan,
+            source: String::new(),
+            line: error.line,
+            column: Some(error.column),
+            at: String::new(),
+            message: error.message,
+        }
+    }
+}
+
+impl From<parser::ParseError> for LoxError {
+    fn from(error: parser::ParseError) -> Self {
+        LoxError {
+            kind: LoxErrorKind::Parse,
+            source: String::new(),
+            line: error.line,
+            column: Some(error.column),
+            at: error.at,
+            message: error.message,
+        }
+    }
+}
+
+impl From<parser::SyntaxError> for LoxError {
+    fn from(error: parser::SyntaxError) -> Self {
+        match error {
+            parser::SyntaxError::Scan(error) => error.into(),
+            parser::SyntaxError::Parse(error) => error.into(),
+        }
+    }
+}
+
+/// Settings of the programs run by `run_file`, `run_reader` and `run_prompt`.
+#[derive(Debug, Clone, Copy)]
+pub struct RunOptions {
+    /// Whether warnings are printed, like for unused variables.
+    pub warnings: bool,
+    /// Whether how many statements and expressions were evaluated is printed at the end.
+    pub profile: bool,
+    /// How many lines of output programs run from a file or stdin can print. The rest is dropped.
+    pub max_output_lines: Option<usize>,
+}
+
+impl Default for RunOptions {
+    fn default() -> Self {
+        RunOptions {
+            warnings: true,
+            profile: false,
+            max_output_lines: None,
+        }
+    }
+}
+
+/// Why running a file failed.
+#[derive(Debug)]
+pub enum LoxRunError {
+    /// The file could not be read.
+    Io(io::Error),
+    /// The program had errors, which have already been printed.
+    Lox(Vec<LoxError>),
+}
+
+impl fmt::Display for LoxRunError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoxRunError::Io(error) => write!(f, "{}", error),
+            LoxRunError::Lox(errors) => {
+                let errors: Vec<String> = errors.iter().map(LoxError::to_string).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+        }
+    }
+}
+
+impl error::Error for LoxRunError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            LoxRunError::Io(error) => Some(error),
+            LoxRunError::Lox(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for LoxRunError {
+    fn from(error: io::Error) -> Self {
+        LoxRunError::Io(error)
+    }
+}
+
+pub fn run_file(filepath: &str, options: &RunOptions) -> Result<(), LoxRunError> {
+    let source = fs::read_to_string(filepath)?;
+    let (mut interpreter, truncated) = program_interpreter(options);
+    interpreter.set_current_file(Path::new(filepath));
+    let errors = run(skip_shebang(&source), filepath, &mut interpreter);
+    print_errors(&errors);
+    print_profile(options, &interpreter);
+    print_truncation(options, &truncated);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(LoxRunError::Lox(errors))
+    }
+}
+
+/// Skips a `#!` line at the start of a script, so that it can be run directly on Unix.
+///
+/// The newline ending it is kept so that line numbers in errors stay the same.
+fn skip_shebang(source: &str) -> &str {
+    if source.starts_with("#!") {
+        &source[source.find('\n').unwrap_or(source.len())..]
+    } else {
+        source
+    }
+}
+
+/// Runs a whole program read from `reader`, e.g. when it is piped to the interpreter.
+pub fn run_reader(mut reader: impl Read, options: &RunOptions) -> io::Result<()> {
+    let mut source = String::new();
+    reader.read_to_string(&mut source)?;
+    let (mut interpreter, truncated) = program_interpreter(options);
+    let errors = run(&source, "<stdin>", &mut interpreter);
+    print_errors(&errors);
+    print_profile(options, &interpreter);
+    print_truncation(options, &truncated);
+    Ok(())
+}
+
+/// Creates an interpreter printing to stdout, up to the maximum number of lines of output.
+///
+/// The returned flag is set if the output was cut.
+fn program_interpreter(options: &RunOptions) -> (interpreter::Interpreter, Rc<Cell<bool>>) {
+    let limit = options.max_output_lines.unwrap_or(usize::MAX);
+    let output = output::LineLimitWriter::new(io::stdout(), limit);
+    let truncated = output.truncated();
+    let mut interpreter = interpreter::Interpreter::with_output(Box::new(output));
+    interpreter.warnings = options.warnings;
+    (interpreter, truncated)
+}
+
+fn print_truncation(options: &RunOptions, truncated: &Cell<bool>) {
+    if let (true, Some(limit)) = (truncated.get(), options.max_output_lines) {
+        eprintln!("[Output truncated after {} lines]", limit);
+    }
+}
+
+pub fn run_prompt(options: &RunOptions) -> io::Result<()> {
+    let mut interpreter = interpreter::Interpreter::new();
+    interpreter.warnings = options.warnings;
+    let mut editor = rustyline::Editor::new().map_err(io::Error::other)?;
+    editor.set_helper(Some(completion::LoxCompleter::default()));
+    repl(&mut interpreter, |interpreter| {
+        if let Some(completer) = editor.helper_mut() {
+            completer.set_names(interpreter.environment.keys());
+        }
+        match editor.readline("> ") {
+            Ok(line) => {
+                editor.add_history_entry(&line).map_err(io::Error::other)?;
+                Ok(Some(line))
+            }
+            // like in a shell, ctrl-c only abandons the line being edited
+            Err(ReadlineError::Interrupted) => Ok(Some(String::new())),
+            Err(ReadlineError::Eof) => Ok(None),
+            Err(ReadlineError::Io(error)) => Err(error),
+            Err(error) => Err(io::Error::other(error)),
+        }
+    })?;
+    print_profile(options, &interpreter);
+    Ok(())
+}
+
+/// Runs the lines returned by `read_line` one after the other, until it returns `None`.
+fn repl(
+    interpreter: &mut interpreter::Interpreter,
+    mut read_line: impl FnMut(&mut interpreter::Interpreter) -> io::Result<Option<String>>,
+) -> io::Result<()> {
+    while let Some(line) = read_line(interpreter)? {
+        print_errors(&run_repl_line(&line, interpreter));
         HAD_ERROR.store(false, Ordering::Relaxed)
     }
     Ok(())
 }
 
-fn run(string: &str, environment: &mut environment::Environment) {
-    let tokens = Scanner::new(string).scan_tokens();
-    let statements = Parser::new(tokens).parse();
-    if !had_error() {
-        for statement in statements {
-            statement
-                .evaluate(environment)
-                .unwrap_or_else(|interpreter::EvaluateError(message)| error(0, &message));
+/// Scans and parses a program without running it, returning the errors found.
+///
+/// Nothing is printed, and the errors don't count towards `had_error`.
+pub fn check(source: &str) -> Vec<LoxError> {
+    parse(source, "", false).err().unwrap_or_default()
+}
+
+/// Evaluates a single expression (without a trailing semicolon) in a new interpreter.
+pub fn eval(source: &str) -> Result<object::LoxObject, Vec<LoxError>> {
+    let expression = Parser::from_source(source)
+        .parse_expression_only()
+        .map_err(|errors| errors.into_iter().map(LoxError::from).collect::<Vec<_>>())?;
+    expression
+        .evaluate(&mut interpreter::Interpreter::new())
+        .map_err(|interpreter::EvaluateError(message)| vec![runtime_error("", message)])
+}
+
+/// Formats an error followed by the line of `source` it is on, with carets under its token.
+pub fn format_error(error: &LoxError, source: &str) -> String {
+    let mut formatted = error.to_string();
+    let line = error
+        .line
+        .checked_sub(1)
+        .and_then(|index| source.split('\n').nth(index));
+    if let Some(line) = line {
+        formatted.push_str(&format!("\n  {}", line.trim_end_matches('\r')));
+        if let Some(column) = error.column {
+            // `at` quotes the lexeme of the token, if there is one
+            let width = error.at.chars().count().saturating_sub(2).max(1);
+            let padding = " ".repeat(column - 1);
+            formatted.push_str(&format!("\n  {}{}", padding, "^".repeat(width)));
         }
     }
+    formatted
 }
 
-pub fn error(line: usize, message: &str) {
-    report(line, "", message)
+/// Formats where an error is, with the name of the source if it is known.
+fn location(source: &str, line: usize) -> String {
+    if source.is_empty() {
+        format!("line {}", line)
+    } else {
+        format!("{}:{}", source, line)
+    }
 }
 
-pub fn error_from_token(token: &tokens::Token, message: &str) {
-    if token.token_type == tokens::TokenType::EOF {
-        report(token.line, "end", message);
+/// Parses a program named `source_name`, like `foo.lox` or `<stdin>`, printing the warnings
+/// found if `warnings` is set. Returns the errors found instead of the statements if there are any.
+pub fn parse(
+    source: &str,
+    source_name: &str,
+    warnings: bool,
+) -> Result<Vec<stmt::Stmt>, Vec<LoxError>> {
+    let (statements, errors, found) = Parser::from_source(source).parse();
+    if warnings {
+        found
+            .iter()
+            .for_each(|warning| print_warning(source_name, warning));
+    }
+    if errors.is_empty() {
+        Ok(statements)
     } else {
-        report(token.line, &format!("'{}'", token.lexeme), message);
+        Err(errors
+            .into_iter()
+            .map(|error| located(source_name, error))
+            .collect())
+    }
+}
+
+/// Runs a program, returning its errors.
+fn run(
+    string: &str,
+    source_name: &str,
+    interpreter: &mut interpreter::Interpreter,
+) -> Vec<LoxError> {
+    match parse(string, source_name, interpreter.warnings) {
+        Ok(statements) => execute(statements, source_name, interpreter),
+        Err(errors) => errors,
+    }
+}
+
+/// Runs a line typed in the REPL, printing the value of an expression written without a semicolon.
+/// Returns the errors of the line.
+fn run_repl_line(line: &str, interpreter: &mut interpreter::Interpreter) -> Vec<LoxError> {
+    let (tokens, scan_errors) = Scanner::new(line).scan_tokens();
+    let mut errors: Vec<LoxError> = scan_errors
+        .into_iter()
+        .map(|error| located(REPL_SOURCE, error))
+        .collect();
+    match Parser::parse_repl_line(line, tokens) {
+        parser::ReplLine::Statements(..) | parser::ReplLine::Expression(_)
+            if !errors.is_empty() => {}
+        parser::ReplLine::Statements(statements, warnings) => {
+            if interpreter.warnings {
+                warnings
+                    .iter()
+                    .for_each(|warning| print_warning(REPL_SOURCE, warning));
+            }
+            errors = execute(statements, REPL_SOURCE, interpreter);
+        }
+        parser::ReplLine::Expression(expression) => match expression.evaluate(interpreter) {
+            // like statements, calls to functions returning nothing show nothing
+            Ok(object::LoxObject::Nil) => {}
+            Ok(value) => {
+                if writeln!(interpreter.output(), "{}", value).is_err() {
+                    errors.push(runtime_error(
+                        REPL_SOURCE,
+                        "Could not write output.".to_string(),
+                    ))
+                }
+            }
+            Err(interpreter::EvaluateError(message)) => {
+                errors.push(runtime_error(REPL_SOURCE, message))
+            }
+        },
+        parser::ReplLine::Empty => {}
+        parser::ReplLine::Error(syntax_errors) => errors.extend(
+            syntax_errors
+                .into_iter()
+                .map(|error| located(REPL_SOURCE, error)),
+        ),
+    }
+    errors
+}
+
+/// Runs parsed statements, returning the runtime errors.
+fn execute(
+    statements: Vec<stmt::Stmt>,
+    source_name: &str,
+    interpreter: &mut interpreter::Interpreter,
+) -> Vec<LoxError> {
+    let statements = optimizer::fold_constants(optimizer::remove_dead_code(statements));
+    match interpreter::execute_program(statements, interpreter) {
+        Ok(()) => vec![],
+        Err(errors) => errors
+            .into_iter()
+            .map(|interpreter::EvaluateError(message)| runtime_error(source_name, message))
+            .collect(),
+    }
+}
+
+/// Converts an error, giving it the name of the source it is in.
+fn located(source_name: &str, error: impl Into<LoxError>) -> LoxError {
+    LoxError {
+        source: source_name.to_string(),
+        ..error.into()
+    }
+}
+
+fn runtime_error(source_name: &str, message: String) -> LoxError {
+    LoxError {
+        kind: LoxErrorKind::Runtime,
+        source: source_name.to_string(),
+        line: 0,
+        column: None,
+        at: String::new(),
+        message,
+    }
+}
+
+/// Prints errors, after which `had_error` returns true.
+fn print_errors(errors: &[LoxError]) {
+    for error in errors {
+        eprintln!("{}", error);
+        HAD_ERROR.store(true, Ordering::Relaxed)
     }
 }
 
-fn report(line: usize, at: &str, message: &str) {
-    eprintln!("[line {}] Error at {}: {}", line, at, message);
-    HAD_ERROR.store(true, Ordering::Relaxed)
+/// Prints a warning, which unlike an error doesn't stop the program from running.
+fn print_warning(source_name: &str, warning: &parser::Warning) {
+    eprintln!(
+        "[{}] Warning at {}: {}",
+        location(source_name, warning.line),
+        warning.at,
+        warning.message
+    );
+}
+
+fn print_profile(options: &RunOptions, interpreter: &interpreter::Interpreter) {
+    if options.profile {
+        eprintln!(
+            "Profiler: {} statements, {} expressions evaluated",
+            interpreter.statements_evaluated, interpreter.expressions_evaluated
+        );
+    }
 }
 
 pub fn had_error() -> bool {
     HAD_ERROR.load(Ordering::Relaxed)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SharedOutput;
+
+    #[test]
+    fn valid_programs_have_no_errors() {
+        assert_eq!(
+            check("var a = 1;\nwhile (a < 10) a = a + 1;\nprint(a);"),
+            vec![]
+        );
+        // runtime errors are not detected without running the program
+        assert_eq!(check("print(-\"a\");"), vec![]);
+    }
+
+    #[test]
+    fn scanner_errors_are_returned() {
+        assert_eq!(
+            check("print(1);\n  @"),
+            vec![LoxError {
+                kind: LoxErrorKind::Scan,
+                source: String::new(),
+                line: 2,
+                column: Some(3),
+                at: String::new(),
+                message: "Unexpected character.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn parser_errors_are_returned() {
+        let errors = check("print(1;\nbreak;");
+        assert_eq!(
+            errors,
+            vec![
+                LoxError {
+                    kind: LoxErrorKind::Parse,
+                    source: String::new(),
+                    line: 1,
+                    column: Some(8),
+                    at: "';'".to_string(),
+                    message: "Expect ')' after arguments".to_string(),
+                },
+                LoxError {
+                    kind: LoxErrorKind::Parse,
+                    source: String::new(),
+                    line: 2,
+                    column: Some(1),
+                    at: "'break'".to_string(),
+                    message: "Can't use 'break' outside of a loop.".to_string(),
+                },
+            ]
+        );
+        assert_eq!(
+            errors[1].to_string(),
+            "[line 2, col 1] Error at 'break': Can't use 'break' outside of a loop."
+        );
+    }
+
+    #[test]
+    fn constants_cannot_be_assigned() {
+        let errors = check("const a = 1;\n{ var b = a; const c = b; c = 3; }\na = 2;");
+        let messages: Vec<_> = errors
+            .iter()
+            .map(|error| (error.line, error.message.as_str()))
+            .collect();
+        assert_eq!(
+            messages,
+            [
+                (2, "Can't assign to constant 'c'."),
+                (3, "Can't assign to constant 'a'.")
+            ]
+        );
+        // a local variable shadowing a constant can be assigned
+        assert_eq!(check("const a = 1; { var a = 2; a = 3; }"), vec![]);
+    }
+
+    #[test]
+    fn constants_need_an_initializer() {
+        let errors = check("const a;");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].at, "';'");
+        assert_eq!(errors[0].message, "Expect '=' after constant name.");
+    }
+
+    #[test]
+    fn local_variables_cannot_be_redeclared() {
+        let errors = check("{ var a = 1; { var a = 2; } var a = 3; }");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].at, "'a'");
+        assert_eq!(
+            errors[0].message,
+            "Already a variable with the name 'a' in this scope."
+        );
+        // globals can be redefined, also across lines of the REPL which are parsed separately
+        assert_eq!(check("var a = 1; var a = 2;"), vec![]);
+    }
+
+    #[test]
+    fn eval_returns_the_value_of_an_expression() {
+        assert_eq!(eval("1 + 2"), Ok(object::LoxObject::Number(3.0)));
+        assert_eq!(eval("nil"), Ok(object::LoxObject::Nil));
+        assert_eq!(eval("str_upper(\"lox\")"), Ok("LOX".to_string().into()));
+        assert_eq!(eval("1 / 0"), Ok(object::LoxObject::Number(f32::INFINITY)));
+    }
+
+    #[test]
+    fn eval_names_undefined_variables() {
+        let errors = eval("missing + 1").unwrap_err();
+        assert_eq!(errors[0].message, "Undefined variable 'missing'.");
+    }
+
+    #[test]
+    fn eval_rejects_statements() {
+        let errors = eval("print(1);").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LoxErrorKind::Parse);
+        assert_eq!(errors[0].message, "Expect end of expression.");
+        assert_eq!(eval("var a = 1").unwrap_err()[0].kind, LoxErrorKind::Parse);
+        assert_eq!(eval("1 2").unwrap_err()[0].at, "'2'");
+    }
+
+    #[test]
+    fn eval_returns_runtime_errors() {
+        let errors = eval("-\"a\"").unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LoxErrorKind::Runtime);
+        assert_eq!(errors[0].message, "cannot negate a non-number, got string");
+    }
+
+    #[test]
+    fn errors_name_their_source() {
+        let errors = parse("var a = 1;\nvar = 2;", "foo.lox", false)
+            .err()
+            .unwrap();
+        assert_eq!(errors[0].source, "foo.lox");
+        assert_eq!(
+            errors[0].to_string(),
+            "[foo.lox:2, col 5] Error at '=': Expect variable name."
+        );
+        let errors = parse("1 @ 2;", "foo.lox", false).err().unwrap();
+        assert_eq!(
+            errors[0].to_string(),
+            "[foo.lox:1, col 3] Error at : Unexpected character."
+        );
+        assert_eq!(check("var = 2;")[0].source, "");
+    }
+
+    #[test]
+    fn repl_shows_the_values_of_expressions() {
+        let output = SharedOutput::default();
+        let mut interpreter = interpreter::Interpreter::with_output(Box::new(output.clone()));
+        let errors: Vec<LoxError> = ["var a = 1;", "a + 1", "", "print(\"p\")", "a = 5", "-\"a\""]
+            .into_iter()
+            .flat_map(|line| run_repl_line(line, &mut interpreter))
+            .collect();
+        assert_eq!(String::from_utf8(output.0.take()).unwrap(), "2\np\n5\n");
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].kind, LoxErrorKind::Runtime);
+    }
+
+    #[test]
+    fn repl_errors_are_in_the_repl() {
+        let mut interpreter = interpreter::Interpreter::with_output(Box::new(io::sink()));
+        let errors: Vec<LoxError> = ["print(1 +);", "-\"a\";"]
+            .into_iter()
+            .flat_map(|line| run_repl_line(line, &mut interpreter))
+            .collect();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|error| error.source == "<repl>"));
+    }
+
+    #[test]
+    fn formatted_errors_point_at_their_token() {
+        let source = "var x = 1\nprint(x);";
+        let errors = parse(source, "foo.lox", false).err().unwrap();
+        assert_eq!(
+            format_error(&errors[0], source),
+            [
+                "[foo.lox:2, col 1] Error at 'print': Expect ';' after variable declaration.",
+                "  print(x);",
+                "  ^^^^^",
+            ]
+            .join("\n")
+        );
+
+        let source = "print(1);\nvar y = 2 + ;";
+        let errors = check(source);
+        assert_eq!(
+            format_error(&errors[0], source),
+            [
+                "[line 2, col 13] Error at ';': Expected expression.",
+                "  var y = 2 + ;",
+                "              ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn formatted_errors_on_the_first_line() {
+        let source = "var a = 1 @ 2;\nprint(a);";
+        let errors = check(source);
+        assert_eq!(
+            format_error(&errors[0], source),
+            [
+                "[line 1, col 11] Error at : Unexpected character.",
+                "  var a = 1 @ 2;",
+                "            ^",
+            ]
+            .join("\n")
+        );
+        // errors without a line, like runtime errors, have nothing to point at
+        let error = LoxError {
+            kind: LoxErrorKind::Runtime,
+            source: String::new(),
+            line: 0,
+            column: None,
+            at: String::new(),
+            message: "oops".to_string(),
+        };
+        assert_eq!(format_error(&error, source), "[line 0] Error at : oops");
+    }
+
+    #[test]
+    fn interpolations_must_be_closed() {
+        let errors = check("print(\"a ${1 2}\");");
+        assert_eq!(errors[0].at, "'2'");
+        assert_eq!(
+            errors[0].message,
+            "Expect '}' after interpolated expression."
+        );
+        let errors = check("print(\"a ${1");
+        assert_eq!(errors[0].message, "Unterminated string.");
+    }
+
+    #[test]
+    fn check_does_not_report_errors() {
+        check("1 +;");
+        assert!(!had_error());
+    }
+}
@@ -1,32 +1,97 @@
 use std::io;
+use std::io::IsTerminal;
 use std::process::exit;
-
-mod environment;
-mod expr;
-mod interpreter;
-mod lox;
-mod object;
-mod parser;
-mod scanning;
-mod stmt;
-mod tokens;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 
 use clap::Parser;
+use lox::lox;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     filepath: Option<String>,
+    /// Read the program from stdin, even if it is a terminal
+    #[arg(long, conflicts_with = "filepath")]
+    stdin: bool,
+    /// Don't print warnings, like for unused variables
+    #[arg(long)]
+    no_warnings: bool,
+    /// Print how many statements and expressions were evaluated
+    #[arg(long)]
+    profile: bool,
+    /// Stop printing the output of the program after this many lines
+    #[arg(long)]
+    max_output_lines: Option<usize>,
+    /// Stop the program if it still runs after this many seconds
+    #[arg(long, value_parser = parse_seconds)]
+    timeout: Option<Duration>,
+}
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    match arg.parse::<f64>() {
+        Ok(seconds) if seconds.is_finite() && seconds >= 0.0 => {
+            Duration::try_from_secs_f64(seconds)
+                .map_err(|_| format!("'{}' seconds is too long a timeout", arg))
+        }
+        _ => Err(format!("'{}' is not a number of seconds", arg)),
+    }
 }
 
 fn main() -> io::Result<()> {
     let args = Args::parse();
+    let options = lox::RunOptions {
+        warnings: !args.no_warnings,
+        profile: args.profile,
+        max_output_lines: args.max_output_lines,
+    };
     match args.filepath {
-        Some(filepath) => lox::run_file(&filepath)?,
-        None => lox::run_prompt()?,
+        None if !args.stdin && io::stdin().is_terminal() => lox::run_prompt(&options)?,
+        filepath => with_timeout(args.timeout, move || run_program(filepath, &options))?,
     };
     if lox::had_error() {
         exit(65);
     }
     Ok(())
 }
+
+/// Runs the program in the file, or the one read from stdin.
+fn run_program(filepath: Option<String>, options: &lox::RunOptions) -> io::Result<()> {
+    match filepath {
+        Some(filepath) => match lox::run_file(&filepath, options) {
+            Ok(()) => Ok(()),
+            // the errors of the program have already been printed
+            Err(lox::LoxRunError::Lox(_)) => exit(65),
+            Err(lox::LoxRunError::Io(error)) => {
+                eprintln!("Could not read '{}': {}", filepath, error);
+                exit(74);
+            }
+        },
+        None => lox::run_reader(io::stdin(), options),
+    }
+}
+
+/// Runs `f` on a thread of its own, and exits if it doesn't finish within `timeout`.
+fn with_timeout<F>(timeout: Option<Duration>, f: F) -> io::Result<()>
+where
+    F: FnOnce() -> io::Result<()> + Send + 'static,
+{
+    let Some(timeout) = timeout else {
+        return f();
+    };
+    let (sender, receiver) = mpsc::channel();
+    thread::spawn(move || {
+        // if `f` panics, the sender is dropped without sending anything
+        let _ = sender.send(f());
+    });
+    match receiver.recv_timeout(timeout) {
+        Ok(result) => result,
+        Err(mpsc::RecvTimeoutError::Timeout) => {
+            eprintln!("[Timeout after {} seconds]", timeout.as_secs_f64());
+            exit(1);
+        }
+        // the panic message has already been printed by the thread
+        Err(mpsc::RecvTimeoutError::Disconnected) => exit(70),
+    }
+}
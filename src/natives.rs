@@ -0,0 +1,472 @@
+use std::rc::Rc;
+
+use crate::environment;
+use crate::intern;
+use crate::interpreter;
+use crate::object;
+
+use object::LoxObject;
+
+/// Defines the functions available to every program as globals.
+pub fn define_natives(environment: &mut environment::Environment) {
+    let natives = [
+        object::LoxNativeFunction::new("print", None, print),
+        object::LoxNativeFunction::new("input", Some(1), input),
+        object::LoxNativeFunction::new("floor", Some(1), |_, arguments| {
+            math("floor", arguments, f32::floor)
+        }),
+        object::LoxNativeFunction::new("ceil", Some(1), |_, arguments| {
+            math("ceil", arguments, f32::ceil)
+        }),
+        object::LoxNativeFunction::new("round", Some(1), |_, arguments| {
+            math("round", arguments, f32::round)
+        }),
+        object::LoxNativeFunction::new("abs", Some(1), |_, arguments| {
+            math("abs", arguments, f32::abs)
+        }),
+        object::LoxNativeFunction::new("sqrt", Some(1), |_, arguments| {
+            math("sqrt", arguments, f32::sqrt)
+        }),
+        object::LoxNativeFunction::new("pow", Some(2), pow),
+        object::LoxNativeFunction::new("len", Some(1), len),
+        object::LoxNativeFunction::new("str_len", Some(1), str_len),
+        object::LoxNativeFunction::new("str_slice", Some(3), str_slice),
+        object::LoxNativeFunction::new("str_upper", Some(1), |_, arguments| {
+            Ok(string("str_upper", &arguments[0])?.to_uppercase().into())
+        }),
+        object::LoxNativeFunction::new("str_lower", Some(1), |_, arguments| {
+            Ok(string("str_lower", &arguments[0])?.to_lowercase().into())
+        }),
+        object::LoxNativeFunction::new("str_find", Some(2), str_find),
+        object::LoxNativeFunction::new("to_string", Some(1), |_, arguments| {
+            Ok(arguments[0].to_string().into())
+        }),
+        object::LoxNativeFunction::new("to_number", Some(1), to_number),
+        object::LoxNativeFunction::new("assert_eq", Some(3), assert_eq),
+    ];
+    for native in natives {
+        environment.define(
+            intern::intern(&native.name),
+            LoxObject::NativeFunction(Rc::new(native)),
+        );
+    }
+}
+
+/// Prints its arguments separated by spaces, followed by a newline.
+fn print(
+    interpreter: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let line = arguments
+        .iter()
+        .map(|argument| argument.to_string())
+        .collect::<Vec<_>>()
+        .join(" ");
+    writeln!(interpreter.output(), "{}", line)
+        .map_err(|_| interpreter::EvaluateError("Could not write output.".to_string()))?;
+    Ok(LoxObject::Nil)
+}
+
+/// Prints `prompt` and reads a line, without its line ending. Returns nil at the end of the input.
+fn input(
+    interpreter: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let io_error = |_| interpreter::EvaluateError("Could not read input.".to_string());
+    write!(interpreter.output(), "{}", arguments[0]).map_err(io_error)?;
+    interpreter.output().flush().map_err(io_error)?;
+    let mut line = String::new();
+    if interpreter.input().read_line(&mut line).map_err(io_error)? == 0 {
+        return Ok(LoxObject::Nil);
+    }
+    let line = line.strip_suffix('\n').unwrap_or(&line);
+    let line = line.strip_suffix('\r').unwrap_or(line);
+    Ok(LoxObject::String(line.to_string()))
+}
+
+fn number(name: &str, argument: &LoxObject) -> Result<f32, interpreter::EvaluateError> {
+    match argument {
+        LoxObject::Number(n) => Ok(*n),
+        other => Err(interpreter::EvaluateError(format!(
+            "{}() expects numbers as arguments, got {}.",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Applies `operation` to the single number argument of the function `name`.
+fn math(
+    name: &str,
+    arguments: Vec<LoxObject>,
+    operation: fn(f32) -> f32,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    Ok(LoxObject::Number(operation(number(name, &arguments[0])?)))
+}
+
+fn pow(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let base = number("pow", &arguments[0])?;
+    let exponent = number("pow", &arguments[1])?;
+    Ok(LoxObject::Number(base.powf(exponent)))
+}
+
+fn string<'a>(name: &str, argument: &'a LoxObject) -> Result<&'a str, interpreter::EvaluateError> {
+    match argument {
+        LoxObject::String(s) => Ok(s),
+        other => Err(interpreter::EvaluateError(format!(
+            "{}() expects a string, got {}.",
+            name,
+            other.type_name()
+        ))),
+    }
+}
+
+/// Number of elements in a tuple, or of characters in a string.
+fn len(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let length = match &arguments[0] {
+        LoxObject::Tuple(elements) => elements.len(),
+        LoxObject::String(s) => s.chars().count(),
+        other => {
+            return Err(interpreter::EvaluateError(format!(
+                "len() expects a tuple or a string, got {}.",
+                other.type_name()
+            )))
+        }
+    };
+    Ok(LoxObject::Number(length as f32))
+}
+
+/// Number of characters (code points) in a string.
+fn str_len(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let length = string("str_len", &arguments[0])?.chars().count();
+    Ok(LoxObject::Number(length as f32))
+}
+
+/// Characters from `start` up to `end` excluded. Negative indices count from the end.
+fn str_slice(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let s = string("str_slice", &arguments[0])?;
+    let length = s.chars().count() as f32;
+    let index = |argument| {
+        let index = number("str_slice", argument)?;
+        let index = if index < 0.0 { length + index } else { index };
+        if index.fract() != 0.0 || !(0.0..=length).contains(&index) {
+            return Err(interpreter::EvaluateError(
+                "str_slice() index out of range.".to_string(),
+            ));
+        }
+        Ok(index as usize)
+    };
+    let start = index(&arguments[1])?;
+    let end = index(&arguments[2])?;
+    Ok(s.chars()
+        .skip(start)
+        .take(end.saturating_sub(start))
+        .collect::<String>()
+        .into())
+}
+
+/// Index (in characters) of the first occurrence of `needle`, or -1 if there is none.
+fn str_find(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let s = string("str_find", &arguments[0])?;
+    let needle = string("str_find", &arguments[1])?;
+    let index = match s.find(needle) {
+        Some(byte_index) => s[..byte_index].chars().count() as f32,
+        None => -1.0,
+    };
+    Ok(LoxObject::Number(index))
+}
+
+/// Parses a number, ignoring surrounding whitespace. Returns nil if the string is not a number.
+fn to_number(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    match string("to_number", &arguments[0])?.trim().parse() {
+        Ok(n) => Ok(LoxObject::Number(n)),
+        Err(_) => Ok(LoxObject::Nil),
+    }
+}
+
+/// Fails with `message` if `actual` is not equal to `expected`.
+fn assert_eq(
+    _: &mut interpreter::Interpreter,
+    arguments: Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError> {
+    let [expected, actual, message] = <[LoxObject; 3]>::try_from(arguments).unwrap();
+    if expected == actual {
+        Ok(LoxObject::Nil)
+    } else {
+        Err(interpreter::EvaluateError(format!(
+            "Expected {} but got {}: {}",
+            expected, actual, message
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io;
+
+    use super::*;
+    use crate::interpreter::Interpret;
+    use crate::output::SharedOutput;
+    use crate::parser::Parser;
+
+    fn try_run(source: &str) -> Result<String, String> {
+        try_run_with_input(source, "")
+    }
+
+    fn try_run_with_input(source: &str, input: &'static str) -> Result<String, String> {
+        let output = SharedOutput::default();
+        let mut interpreter = interpreter::Interpreter::with_io(
+            Box::new(io::Cursor::new(input.as_bytes())),
+            Box::new(output.clone()),
+        );
+        for statement in Parser::from_source(source).parse().0 {
+            match statement.evaluate(&mut interpreter) {
+                Ok(()) => {}
+                Err(interpreter::Signal::Error(error)) => return Err(error.0),
+                Err(signal) => panic!("unexpected {:?}", signal),
+            }
+        }
+        let printed = output.0.borrow().clone();
+        Ok(String::from_utf8(printed).unwrap())
+    }
+
+    fn run(source: &str) -> String {
+        try_run(source).unwrap()
+    }
+
+    fn run_error(source: &str) -> String {
+        try_run(source).unwrap_err()
+    }
+
+    #[test]
+    fn print_writes_a_line() {
+        assert_eq!(run("print(1 + 2);"), "3\n");
+        assert_eq!(run("print();"), "\n");
+    }
+
+    #[test]
+    fn print_separates_arguments_with_spaces() {
+        assert_eq!(run("print(\"a\", 1, nil, true);"), "a 1 nil true\n");
+    }
+
+    #[test]
+    fn print_is_a_first_class_value() {
+        assert_eq!(run("var f = print; f(42);"), "42\n");
+        assert_eq!(run("print(print);"), "<native fn>\n");
+    }
+
+    #[test]
+    fn passing_assertions_do_nothing() {
+        assert_eq!(run("assert_eq(4, 2 * 2, \"math\"); print(\"ok\");"), "ok\n");
+    }
+
+    #[test]
+    fn failing_assert_eq_reports_both_values() {
+        assert_eq!(
+            run_error("assert_eq(4, 2 + 3, \"addition\");"),
+            "Expected 4 but got 5: addition"
+        );
+    }
+
+    #[test]
+    fn input_reads_a_line() {
+        let output = try_run_with_input(
+            "var name = input(\"name? \"); print(name + \"!\");",
+            "lox\nrest\n",
+        );
+        assert_eq!(output.unwrap(), "name? lox!\n");
+    }
+
+    #[test]
+    fn input_strips_windows_line_endings() {
+        let output = try_run_with_input("print(input(\"\") == \"lox\");", "lox\r\n");
+        assert_eq!(output.unwrap(), "true\n");
+    }
+
+    #[test]
+    fn input_returns_nil_at_the_end_of_the_input() {
+        let output = try_run_with_input("print(input(\"> \"));", "");
+        assert_eq!(output.unwrap(), "> nil\n");
+    }
+
+    #[test]
+    fn input_takes_a_prompt() {
+        assert_eq!(
+            run_error("input();"),
+            "'input' expected 1 arguments but got 0."
+        );
+    }
+
+    #[test]
+    fn rounding_functions() {
+        assert_eq!(run("print(floor(1.5), ceil(1.5), round(1.5));"), "1 2 2\n");
+        assert_eq!(
+            run("print(floor(-1.5), ceil(-1.5), round(-1.5));"),
+            "-2 -1 -2\n"
+        );
+    }
+
+    #[test]
+    fn abs_and_sqrt() {
+        assert_eq!(run("print(abs(-5), abs(5));"), "5 5\n");
+        assert_eq!(run("print(sqrt(16), sqrt(0));"), "4 0\n");
+        assert_eq!(run("print(sqrt(-1));"), "nan\n");
+    }
+
+    #[test]
+    fn pow_raises_to_a_power() {
+        assert_eq!(
+            run("print(pow(2, 10), pow(2, -1), pow(4, 0.5));"),
+            "1024 0.5 2\n"
+        );
+    }
+
+    #[test]
+    fn math_functions_expect_numbers() {
+        assert_eq!(
+            run_error("floor(\"1.5\");"),
+            "floor() expects numbers as arguments, got string."
+        );
+        assert_eq!(
+            run_error("pow(2, nil);"),
+            "pow() expects numbers as arguments, got nil."
+        );
+        assert_eq!(
+            run_error("sqrt(1, 2);"),
+            "'sqrt' expected 1 arguments but got 2."
+        );
+    }
+
+    #[test]
+    fn str_len_counts_characters() {
+        assert_eq!(run("print(str_len(\"lox\"), str_len(\"\"));"), "3 0\n");
+        assert_eq!(
+            run("print(str_len(\"héllo wörld\"), str_len(\"日本\"));"),
+            "11 2\n"
+        );
+    }
+
+    #[test]
+    fn str_slice_takes_character_indices() {
+        assert_eq!(run("print(str_slice(\"lox-rust\", 4, 8));"), "rust\n");
+        assert_eq!(run("print(str_slice(\"lox-rust\", -4, -1));"), "rus\n");
+        assert_eq!(run("print(str_slice(\"日本語\", 1, 3));"), "本語\n");
+        assert_eq!(run("print(str_slice(\"lox\", 2, 1) == \"\");"), "true\n");
+        assert_eq!(run("print(str_slice(\"\", 0, 0) == \"\");"), "true\n");
+    }
+
+    #[test]
+    fn str_slice_rejects_indices_out_of_range() {
+        assert_eq!(
+            run_error("str_slice(\"lox\", 0, 4);"),
+            "str_slice() index out of range."
+        );
+        assert_eq!(
+            run_error("str_slice(\"lox\", -4, 1);"),
+            "str_slice() index out of range."
+        );
+        assert_eq!(
+            run_error("str_slice(\"lox\", 0.5, 1);"),
+            "str_slice() index out of range."
+        );
+    }
+
+    #[test]
+    fn str_upper_and_lower() {
+        assert_eq!(
+            run("print(str_upper(\"Straße\"), str_lower(\"ÀB\"));"),
+            "STRASSE àb\n"
+        );
+        assert_eq!(run("print(str_upper(\"\") == \"\");"), "true\n");
+    }
+
+    #[test]
+    fn str_find_returns_a_character_index() {
+        assert_eq!(run("print(str_find(\"lox-rust\", \"rust\"));"), "4\n");
+        assert_eq!(run("print(str_find(\"日本語\", \"語\"));"), "2\n");
+        assert_eq!(run("print(str_find(\"lox\", \"rust\"));"), "-1\n");
+        assert_eq!(run("print(str_find(\"lox\", \"\"));"), "0\n");
+    }
+
+    #[test]
+    fn string_functions_expect_strings() {
+        assert_eq!(
+            run_error("str_len(1);"),
+            "str_len() expects a string, got number."
+        );
+        assert_eq!(
+            run_error("str_find(\"1\", 1);"),
+            "str_find() expects a string, got number."
+        );
+    }
+
+    #[test]
+    fn to_string_matches_print() {
+        assert_eq!(run("print(to_string(true) == \"true\");"), "true\n");
+        assert_eq!(run("print(to_string(nil) == \"nil\");"), "true\n");
+        assert_eq!(run("print(to_string(1.5) + \"!\");"), "1.5!\n");
+    }
+
+    #[test]
+    fn to_number_parses_strings() {
+        assert_eq!(run("print(to_number(\"3.14\") == 3.14);"), "true\n");
+        assert_eq!(run("print(to_number(\"  42  \"));"), "42\n");
+        assert_eq!(run("print(to_number(\"abc\"));"), "nil\n");
+        assert_eq!(run("print(to_number(\"\"));"), "nil\n");
+    }
+
+    #[test]
+    fn conversions_check_their_arguments() {
+        assert_eq!(
+            run_error("to_number(nil);"),
+            "to_number() expects a string, got nil."
+        );
+        assert_eq!(
+            run_error("to_string();"),
+            "'to_string' expected 1 arguments but got 0."
+        );
+    }
+
+    #[test]
+    fn calls_check_the_number_of_arguments() {
+        assert_eq!(
+            run_error("pow(1, 2, 3);"),
+            "'pow' expected 2 arguments but got 3."
+        );
+        assert_eq!(
+            run_error("pow(1);"),
+            "'pow' expected 2 arguments but got 1."
+        );
+    }
+
+    #[test]
+    fn functions_without_parameters_reject_arguments() {
+        let mut interpreter = interpreter::Interpreter::with_output(Box::new(io::sink()));
+        interpreter.define_native_fn("nothing", 0, |_| Ok(LoxObject::Nil));
+        let statements = Parser::from_source("nothing(1);").parse().0;
+        match statements[0].evaluate(&mut interpreter) {
+            Err(interpreter::Signal::Error(error)) => {
+                assert_eq!(error.0, "'nothing' expected 0 arguments but got 1.")
+            }
+            result => panic!("unexpected {:?}", result),
+        }
+    }
+}
@@ -1,3 +1,17 @@
+//! Values of Lox programs.
+//!
+//! Numbers follow IEEE 754, like the `f32` they are stored in: `0 / 0` is NaN, which is not equal
+//! to anything, itself included, so `nan == nan` is false and `nan != nan` is true.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
+use std::mem;
+use std::rc::Rc;
+
+use crate::interpreter;
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum LoxObject {
     Number(f32),
@@ -5,6 +19,150 @@ pub enum LoxObject {
     True,
     False,
     Nil,
+    NativeFunction(Rc<LoxNativeFunction>),
+    /// A fixed sequence of values, which can't be modified.
+    Tuple(Rc<Vec<LoxObject>>),
+}
+
+impl LoxObject {
+    /// Whether the value counts as true in a condition. Only `false`, `nil`, zero and the empty
+    /// string don't.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            LoxObject::Number(n) => *n != 0.0,
+            LoxObject::String(s) => !s.is_empty(),
+            LoxObject::True => true,
+            LoxObject::False => false,
+            LoxObject::Nil => false,
+            LoxObject::NativeFunction(_) | LoxObject::Tuple(_) => true,
+        }
+    }
+
+    /// Name of the value's type, for error messages.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            LoxObject::Number(_) => "number",
+            LoxObject::String(_) => "string",
+            LoxObject::True | LoxObject::False => "boolean",
+            LoxObject::Nil => "nil",
+            LoxObject::NativeFunction(_) => "function",
+            LoxObject::Tuple(_) => "tuple",
+        }
+    }
+
+    /// The number, if the value is one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            LoxObject::Number(n) => Some((*n).into()),
+            _ => None,
+        }
+    }
+
+    /// The string, if the value is one.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            LoxObject::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The boolean, if the value is `true` or `false`. Other values aren't converted by truthiness.
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            LoxObject::True => Some(true),
+            LoxObject::False => Some(false),
+            _ => None,
+        }
+    }
+}
+
+pub type NativeFn = dyn Fn(
+    &mut interpreter::Interpreter,
+    Vec<LoxObject>,
+) -> Result<LoxObject, interpreter::EvaluateError>;
+
+/// A function implemented in Rust and callable from Lox.
+pub struct LoxNativeFunction {
+    pub name: String,
+    /// Number of arguments the function takes, or `None` if it takes any number of them.
+    pub arity: Option<usize>,
+    pub function: Box<NativeFn>,
+}
+
+impl LoxNativeFunction {
+    pub fn new(
+        name: &str,
+        arity: Option<usize>,
+        function: impl Fn(
+                &mut interpreter::Interpreter,
+                Vec<LoxObject>,
+            ) -> Result<LoxObject, interpreter::EvaluateError>
+            + 'static,
+    ) -> Self {
+        LoxNativeFunction {
+            name: name.to_string(),
+            arity,
+            function: Box::new(function),
+        }
+    }
+}
+
+impl fmt::Debug for LoxNativeFunction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("LoxNativeFunction")
+            .field("name", &self.name)
+            .field("arity", &self.arity)
+            .finish_non_exhaustive()
+    }
+}
+
+impl PartialEq for LoxNativeFunction {
+    fn eq(&self, other: &Self) -> bool {
+        // functions are only equal to themselves
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Numbers, strings (lexicographically) and booleans (`false < true`) can be ordered between
+/// values of the same type. `nil` is less than everything else, and functions and tuples are not
+/// ordered.
+///
+/// Lox programs still can't compare `nil`: the interpreter reports that as a runtime error.
+impl PartialOrd for LoxObject {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (LoxObject::Number(n1), LoxObject::Number(n2)) => n1.partial_cmp(n2),
+            (LoxObject::String(s1), LoxObject::String(s2)) => Some(s1.cmp(s2)),
+            (LoxObject::True | LoxObject::False, LoxObject::True | LoxObject::False) => {
+                Some(self.is_truthy().cmp(&other.is_truthy()))
+            }
+            (LoxObject::Nil, LoxObject::Nil) => Some(Ordering::Equal),
+            (LoxObject::Nil, _) => Some(Ordering::Less),
+            (_, LoxObject::Nil) => Some(Ordering::Greater),
+            _ if self == other => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+/// Lets values be keys of a `HashMap`. Equality is still that of `f32` for numbers though, so a NaN
+/// key can be inserted but never found again, since it is not equal to itself.
+impl Eq for LoxObject {}
+
+/// Hashes values consistently with their equality: numbers by their bits (with `-0` hashed like
+/// `0`, which it is equal to), and functions by their address.
+impl Hash for LoxObject {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+            LoxObject::Number(n) if *n == 0.0 => 0.0f32.to_bits().hash(state),
+            LoxObject::Number(n) => n.to_bits().hash(state),
+            LoxObject::String(s) => s.hash(state),
+            LoxObject::True | LoxObject::False | LoxObject::Nil => {}
+            LoxObject::NativeFunction(f) => Rc::as_ptr(f).hash(state),
+            LoxObject::Tuple(elements) => elements.hash(state),
+        }
+    }
 }
 
 impl From<f32> for LoxObject {
@@ -13,6 +171,13 @@ impl From<f32> for LoxObject {
     }
 }
 
+/// Numbers are `f32`, so this loses precision.
+impl From<f64> for LoxObject {
+    fn from(f: f64) -> Self {
+        LoxObject::Number(f as f32)
+    }
+}
+
 impl From<bool> for LoxObject {
     fn from(b: bool) -> Self {
         if b {
@@ -29,14 +194,268 @@ impl From<String> for LoxObject {
     }
 }
 
+/// The error of a failed conversion from a Lox value, like an argument of a native function.
+fn expected(type_name: &str, obj: &LoxObject) -> interpreter::EvaluateError {
+    interpreter::EvaluateError(format!("Expected {}, got {}.", type_name, obj.type_name()))
+}
+
+impl TryFrom<LoxObject> for f64 {
+    type Error = interpreter::EvaluateError;
+
+    fn try_from(obj: LoxObject) -> Result<Self, Self::Error> {
+        match obj {
+            LoxObject::Number(n) => Ok(n.into()),
+            _ => Err(expected("number", &obj)),
+        }
+    }
+}
+
+impl TryFrom<LoxObject> for bool {
+    type Error = interpreter::EvaluateError;
+
+    fn try_from(obj: LoxObject) -> Result<Self, Self::Error> {
+        match obj {
+            LoxObject::True => Ok(true),
+            LoxObject::False => Ok(false),
+            _ => Err(expected("boolean", &obj)),
+        }
+    }
+}
+
+impl TryFrom<LoxObject> for String {
+    type Error = interpreter::EvaluateError;
+
+    fn try_from(obj: LoxObject) -> Result<Self, Self::Error> {
+        match obj {
+            LoxObject::String(s) => Ok(s),
+            _ => Err(expected("string", &obj)),
+        }
+    }
+}
+
 impl std::fmt::Display for LoxObject {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
+            LoxObject::Number(n) if n.is_nan() => write!(f, "nan"),
             LoxObject::Number(n) => write!(f, "{}", n),
             LoxObject::String(s) => write!(f, "{}", s),
             LoxObject::True => write!(f, "true"),
             LoxObject::False => write!(f, "false"),
             LoxObject::Nil => write!(f, "nil"),
+            LoxObject::NativeFunction(_) => write!(f, "<native fn>"),
+            // a single element is followed by a comma, like in the source
+            LoxObject::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            LoxObject::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, ")")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn truthiness() {
+        assert!(!LoxObject::Number(0.0).is_truthy());
+        assert!(LoxObject::Number(1.0).is_truthy());
+        assert!(LoxObject::Number(-0.5).is_truthy());
+        assert!(!LoxObject::String(String::new()).is_truthy());
+        assert!(LoxObject::String("a".to_string()).is_truthy());
+        assert!(LoxObject::True.is_truthy());
+        assert!(!LoxObject::False.is_truthy());
+        assert!(!LoxObject::Nil.is_truthy());
+        let native = LoxNativeFunction::new("f", Some(0), |_, _| Ok(LoxObject::Nil));
+        assert!(LoxObject::NativeFunction(Rc::new(native)).is_truthy());
+    }
+
+    #[test]
+    fn accessors() {
+        let native = LoxNativeFunction::new("f", Some(0), |_, _| Ok(LoxObject::Nil));
+        let values = [
+            LoxObject::Number(1.5),
+            LoxObject::String("a".to_string()),
+            LoxObject::True,
+            LoxObject::False,
+            LoxObject::Nil,
+            LoxObject::NativeFunction(Rc::new(native)),
+        ];
+        let numbers: Vec<_> = values.iter().map(LoxObject::as_f64).collect();
+        assert_eq!(numbers, [Some(1.5), None, None, None, None, None]);
+        let strings: Vec<_> = values.iter().map(LoxObject::as_str).collect();
+        assert_eq!(strings, [None, Some("a"), None, None, None, None]);
+        let booleans: Vec<_> = values.iter().map(LoxObject::as_bool).collect();
+        assert_eq!(booleans, [None, None, Some(true), Some(false), None, None]);
+    }
+
+    #[test]
+    fn nan_is_not_equal_to_itself() {
+        let nan = LoxObject::Number(f32::NAN);
+        assert_ne!(nan, nan.clone());
+        assert_eq!(nan.partial_cmp(&nan), None);
+        assert_ne!(nan, LoxObject::Number(0.0));
+    }
+
+    #[test]
+    fn values_as_map_keys() {
+        let function = || {
+            Rc::new(LoxNativeFunction::new("f", Some(0), |_, _| {
+                Ok(LoxObject::Nil)
+            }))
+        };
+        let (native, other) = (function(), function());
+        let mut map = HashMap::new();
+        map.insert(LoxObject::Number(1.5), "number");
+        map.insert(LoxObject::Number(0.0), "zero");
+        map.insert(LoxObject::String("a".to_string()), "string");
+        map.insert(LoxObject::True, "true");
+        map.insert(LoxObject::False, "false");
+        map.insert(LoxObject::Nil, "nil");
+        map.insert(LoxObject::NativeFunction(native.clone()), "function");
+        assert_eq!(map.get(&LoxObject::Number(1.5)), Some(&"number"));
+        assert_eq!(map.get(&LoxObject::Number(-0.0)), Some(&"zero"));
+        assert_eq!(
+            map.get(&LoxObject::String("a".to_string())),
+            Some(&"string")
+        );
+        assert_eq!(map.get(&LoxObject::True), Some(&"true"));
+        assert_eq!(map.get(&LoxObject::False), Some(&"false"));
+        assert_eq!(map.get(&LoxObject::Nil), Some(&"nil"));
+        assert_eq!(
+            map.get(&LoxObject::NativeFunction(native)),
+            Some(&"function")
+        );
+        assert_eq!(map.get(&LoxObject::NativeFunction(other)), None);
+        assert_eq!(map.get(&LoxObject::String("1.5".to_string())), None);
+        map.insert(LoxObject::Number(f32::NAN), "nan");
+        assert_eq!(map.get(&LoxObject::Number(f32::NAN)), None);
+    }
+
+    #[test]
+    fn tuples() {
+        let tuple = |elements: Vec<LoxObject>| LoxObject::Tuple(Rc::new(elements));
+        let pair = tuple(vec![LoxObject::Number(1.0), "a".to_string().into()]);
+        assert_eq!(pair.to_string(), "(1, a)");
+        assert_eq!(tuple(vec![pair.clone()]).to_string(), "((1, a),)");
+        assert_eq!(pair.type_name(), "tuple");
+        assert!(pair.is_truthy());
+        assert_eq!(pair, pair.clone());
+        assert_ne!(pair, tuple(vec![LoxObject::Number(1.0)]));
+        assert_eq!(pair.partial_cmp(&pair.clone()), Some(Ordering::Equal));
+        assert_eq!(pair.partial_cmp(&tuple(vec![])), None);
+    }
+
+    #[test]
+    fn number_display() {
+        let display = |n: f32| LoxObject::Number(n).to_string();
+        assert_eq!(display(1.0), "1");
+        assert_eq!(display(2.5), "2.5");
+        assert_eq!(display(1000000.0), "1000000");
+        assert_eq!(display(0.1), "0.1");
+        assert_eq!(display(-0.5), "-0.5");
+        // the shortest representation that reads back as the same f32, like a scanned literal
+        let literal = "1.23456789012345".parse().unwrap();
+        assert_eq!(display(literal), "1.2345679");
+        assert_eq!(display(0.1 + 0.2), "0.3");
+        assert_eq!(display(1e20), "100000000000000000000");
+        assert_eq!(display(f32::INFINITY), "inf");
+        assert_eq!(display(f32::NAN), "nan");
+    }
+
+    #[test]
+    fn type_names() {
+        assert_eq!(LoxObject::Number(1.5).type_name(), "number");
+        assert_eq!(LoxObject::String("a".to_string()).type_name(), "string");
+        assert_eq!(LoxObject::True.type_name(), "boolean");
+        assert_eq!(LoxObject::False.type_name(), "boolean");
+        assert_eq!(LoxObject::Nil.type_name(), "nil");
+        let native = LoxNativeFunction::new("f", Some(0), |_, _| Ok(LoxObject::Nil));
+        assert_eq!(
+            LoxObject::NativeFunction(Rc::new(native)).type_name(),
+            "function"
+        );
+    }
+
+    #[test]
+    fn ordering() {
+        let string = |s: &str| LoxObject::String(s.to_string());
+        assert!(LoxObject::Number(1.0) < LoxObject::Number(2.0));
+        assert!(string("abc") < string("abd"));
+        assert!(string("ab") < string("abc"));
+        assert!(LoxObject::False < LoxObject::True);
+        assert!(LoxObject::Nil <= LoxObject::Nil);
+        assert_eq!(
+            LoxObject::Number(f32::NAN).partial_cmp(&LoxObject::Number(1.0)),
+            None
+        );
+        assert_eq!(LoxObject::Number(1.0).partial_cmp(&string("1")), None);
+    }
+
+    #[test]
+    fn nil_is_less_than_everything() {
+        let others = [
+            LoxObject::Number(f32::NEG_INFINITY),
+            LoxObject::Number(f32::NAN),
+            LoxObject::String(String::new()),
+            LoxObject::False,
+            LoxObject::Tuple(Rc::new(vec![])),
+        ];
+        for other in others {
+            assert_eq!(LoxObject::Nil.partial_cmp(&other), Some(Ordering::Less));
+            assert_eq!(other.partial_cmp(&LoxObject::Nil), Some(Ordering::Greater));
         }
+        assert_eq!(
+            LoxObject::Nil.partial_cmp(&LoxObject::Nil),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn conversions_to_rust_values() {
+        assert_eq!(f64::try_from(LoxObject::Number(1.5)).unwrap(), 1.5);
+        assert!(bool::try_from(LoxObject::True).unwrap());
+        assert!(!bool::try_from(LoxObject::False).unwrap());
+        assert_eq!(
+            String::try_from(LoxObject::String("a".to_string())).unwrap(),
+            "a"
+        );
+    }
+
+    #[test]
+    fn failed_conversions_name_the_types() {
+        let error = f64::try_from(LoxObject::String("1".to_string())).unwrap_err();
+        assert_eq!(error.0, "Expected number, got string.");
+        let error = bool::try_from(LoxObject::Nil).unwrap_err();
+        assert_eq!(error.0, "Expected boolean, got nil.");
+        let error = String::try_from(LoxObject::Number(1.0)).unwrap_err();
+        assert_eq!(error.0, "Expected string, got number.");
+    }
+
+    #[test]
+    fn conversions_in_native_functions() {
+        let repeat = LoxNativeFunction::new("repeat", Some(2), |_, arguments| {
+            let [text, count] = <[LoxObject; 2]>::try_from(arguments).unwrap();
+            let text: String = text.try_into()?;
+            let count: f64 = count.try_into()?;
+            Ok(LoxObject::String(text.repeat(count as usize)))
+        });
+        let mut interpreter = interpreter::Interpreter::with_output(Box::new(std::io::sink()));
+        let arguments = vec![LoxObject::String("ab".to_string()), LoxObject::Number(3.0)];
+        let result = (repeat.function)(&mut interpreter, arguments).unwrap();
+        assert_eq!(result, LoxObject::String("ababab".to_string()));
+
+        let arguments = vec![LoxObject::String("ab".to_string()), LoxObject::Nil];
+        let error = (repeat.function)(&mut interpreter, arguments).unwrap_err();
+        assert_eq!(error.0, "Expected number, got nil.");
     }
 }
@@ -0,0 +1,367 @@
+use std::io;
+
+use crate::expr;
+use crate::interpreter;
+use crate::interpreter::Interpret;
+use crate::stmt;
+
+/// Replaces operations on literals with their result, wherever it can be computed ahead of time.
+///
+/// Operations that would fail at runtime (like adding a number to a string) are kept as they are,
+/// so that the error is still reported when the program runs.
+pub fn fold_constants(statements: Vec<stmt::Stmt>) -> Vec<stmt::Stmt> {
+    // literals never touch the environment, so evaluating them has no side effects
+    let mut interpreter = interpreter::Interpreter::with_output(Box::new(io::sink()));
+    fold_statements(statements, &mut interpreter)
+}
+
+/// Folds statements, evaluating operations on literals with `interpreter`.
+fn fold_statements(
+    statements: Vec<stmt::Stmt>,
+    interpreter: &mut interpreter::Interpreter,
+) -> Vec<stmt::Stmt> {
+    statements
+        .into_iter()
+        .map(|statement| fold_statement(statement, interpreter))
+        .collect()
+}
+
+fn fold_statement(statement: stmt::Stmt, interpreter: &mut interpreter::Interpreter) -> stmt::Stmt {
+    match statement {
+        stmt::Stmt::Expression(expression) => {
+            stmt::Stmt::Expression(fold_expression(expression, interpreter))
+        }
+        stmt::Stmt::Block(statements) => {
+            stmt::Stmt::Block(fold_statements(statements, interpreter))
+        }
+        stmt::Stmt::Var { name, initializer } => stmt::Stmt::Var {
+            name,
+            initializer: initializer.map(|expression| fold_expression(expression, interpreter)),
+        },
+        stmt::Stmt::Const { name, initializer } => stmt::Stmt::Const {
+            name,
+            initializer: fold_expression(initializer, interpreter),
+        },
+        stmt::Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => stmt::Stmt::If {
+            condition: fold_expression(condition, interpreter),
+            then_branch: Box::new(fold_statement(*then_branch, interpreter)),
+            else_branch: else_branch
+                .map(|statement| Box::new(fold_statement(*statement, interpreter))),
+        },
+        stmt::Stmt::While(condition, body) => stmt::Stmt::While(
+            fold_expression(condition, interpreter),
+            Box::new(fold_statement(*body, interpreter)),
+        ),
+        stmt::Stmt::For {
+            label,
+            condition,
+            increment,
+            body,
+        } => stmt::Stmt::For {
+            label,
+            condition: fold_expression(condition, interpreter),
+            increment: increment.map(|expression| fold_expression(expression, interpreter)),
+            body: Box::new(fold_statement(*body, interpreter)),
+        },
+        stmt::Stmt::Loop { body } => stmt::Stmt::Loop {
+            body: Box::new(fold_statement(*body, interpreter)),
+        },
+        stmt::Stmt::Repeat { count, body } => stmt::Stmt::Repeat {
+            count: fold_expression(count, interpreter),
+            body: Box::new(fold_statement(*body, interpreter)),
+        },
+        stmt::Stmt::Break { .. } | stmt::Stmt::Continue { .. } | stmt::Stmt::Import { .. } => {
+            statement
+        }
+        stmt::Stmt::TryCatch {
+            body,
+            catch_var,
+            catch_body,
+            finally_body,
+        } => stmt::Stmt::TryCatch {
+            body: Box::new(fold_statement(*body, interpreter)),
+            catch_var,
+            catch_body: Box::new(fold_statement(*catch_body, interpreter)),
+            finally_body: finally_body
+                .map(|statement| Box::new(fold_statement(*statement, interpreter))),
+        },
+        stmt::Stmt::Throw(value) => stmt::Stmt::Throw(fold_expression(value, interpreter)),
+        stmt::Stmt::Assert {
+            condition,
+            message,
+            source,
+        } => stmt::Stmt::Assert {
+            condition: fold_expression(condition, interpreter),
+            message: message.map(|expression| fold_expression(expression, interpreter)),
+            source,
+        },
+        stmt::Stmt::Export(declaration) => {
+            stmt::Stmt::Export(Box::new(fold_statement(*declaration, interpreter)))
+        }
+    }
+}
+
+/// Removes the statements of blocks which come after a `break` or `continue`, since they can never
+/// run. The parser warns about them.
+pub fn remove_dead_code(statements: Vec<stmt::Stmt>) -> Vec<stmt::Stmt> {
+    let mut live = vec![];
+    for statement in statements {
+        let jumps = matches!(
+            statement,
+            stmt::Stmt::Break { .. } | stmt::Stmt::Continue { .. }
+        );
+        live.push(remove_dead_code_in(statement));
+        if jumps {
+            break;
+        }
+    }
+    live
+}
+
+fn remove_dead_code_in(statement: stmt::Stmt) -> stmt::Stmt {
+    let boxed = |statement: Box<stmt::Stmt>| Box::new(remove_dead_code_in(*statement));
+    match statement {
+        stmt::Stmt::Block(statements) => stmt::Stmt::Block(remove_dead_code(statements)),
+        stmt::Stmt::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => stmt::Stmt::If {
+            condition,
+            then_branch: boxed(then_branch),
+            else_branch: else_branch.map(boxed),
+        },
+        stmt::Stmt::While(condition, body) => stmt::Stmt::While(condition, boxed(body)),
+        stmt::Stmt::For {
+            label,
+            condition,
+            increment,
+            body,
+        } => stmt::Stmt::For {
+            label,
+            condition,
+            increment,
+            body: boxed(body),
+        },
+        stmt::Stmt::Loop { body } => stmt::Stmt::Loop { body: boxed(body) },
+        stmt::Stmt::Repeat { count, body } => stmt::Stmt::Repeat {
+            count,
+            body: boxed(body),
+        },
+        stmt::Stmt::TryCatch {
+            body,
+            catch_var,
+            catch_body,
+            finally_body,
+        } => stmt::Stmt::TryCatch {
+            body: boxed(body),
+            catch_var,
+            catch_body: boxed(catch_body),
+            finally_body: finally_body.map(boxed),
+        },
+        stmt::Stmt::Export(declaration) => stmt::Stmt::Export(boxed(declaration)),
+        stmt::Stmt::Expression(_)
+        | stmt::Stmt::Var { .. }
+        | stmt::Stmt::Const { .. }
+        | stmt::Stmt::Break { .. }
+        | stmt::Stmt::Continue { .. }
+        | stmt::Stmt::Throw(_)
+        | stmt::Stmt::Assert { .. }
+        | stmt::Stmt::Import { .. } => statement,
+    }
+}
+
+fn fold_expression(
+    expression: expr::Expr,
+    interpreter: &mut interpreter::Interpreter,
+) -> expr::Expr {
+    match expression {
+        expr::Expr::Unary(op, operand) => try_evaluate(
+            expr::Expr::Unary(op, Box::new(fold_expression(*operand, interpreter))),
+            interpreter,
+        ),
+        expr::Expr::Binary(lhs, op, rhs) => try_evaluate(
+            expr::Expr::Binary(
+                Box::new(fold_expression(*lhs, interpreter)),
+                op,
+                Box::new(fold_expression(*rhs, interpreter)),
+            ),
+            interpreter,
+        ),
+        expr::Expr::Grouping(inner) => match fold_expression(*inner, interpreter) {
+            expr::Expr::Literal(obj) => expr::Expr::Literal(obj),
+            inner => expr::Expr::Grouping(Box::new(inner)),
+        },
+        expr::Expr::Logical(lhs, op, rhs) => expr::Expr::Logical(
+            Box::new(fold_expression(*lhs, interpreter)),
+            op,
+            Box::new(fold_expression(*rhs, interpreter)),
+        ),
+        expr::Expr::Call { callee, arguments } => expr::Expr::Call {
+            callee: Box::new(fold_expression(*callee, interpreter)),
+            arguments: arguments
+                .into_iter()
+                .map(|expression| fold_expression(expression, interpreter))
+                .collect(),
+        },
+        expr::Expr::Assign(name, value) => {
+            expr::Expr::Assign(name, Box::new(fold_expression(*value, interpreter)))
+        }
+        expr::Expr::StringInterp(parts) => expr::Expr::StringInterp(
+            parts
+                .into_iter()
+                .map(|part| match part {
+                    expr::StringPart::Expr(expression) => {
+                        expr::StringPart::Expr(fold_expression(expression, interpreter))
+                    }
+                    literal => literal,
+                })
+                .collect(),
+        ),
+        expr::Expr::Tuple(elements) => expr::Expr::Tuple(
+            elements
+                .into_iter()
+                .map(|expression| fold_expression(expression, interpreter))
+                .collect(),
+        ),
+        expr::Expr::Index(tuple, index) => expr::Expr::Index(
+            Box::new(fold_expression(*tuple, interpreter)),
+            Box::new(fold_expression(*index, interpreter)),
+        ),
+        expr::Expr::Literal(_) | expr::Expr::Variable(_) => expression,
+    }
+}
+
+/// Evaluates an operation whose operands are already folded, if they are all literals.
+fn try_evaluate(expression: expr::Expr, interpreter: &mut interpreter::Interpreter) -> expr::Expr {
+    let operands_are_literals = match &expression {
+        expr::Expr::Unary(_, operand) => matches!(**operand, expr::Expr::Literal(_)),
+        expr::Expr::Binary(lhs, _, rhs) => matches!(
+            (&**lhs, &**rhs),
+            (expr::Expr::Literal(_), expr::Expr::Literal(_))
+        ),
+        _ => false,
+    };
+    if !operands_are_literals {
+        return expression;
+    }
+    match expression.evaluate(interpreter) {
+        Ok(obj) => expr::Expr::Literal(obj),
+        Err(_) => expression,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::Parser;
+
+    fn fold(source: &str) -> String {
+        let statements = Parser::from_source(source).parse().0;
+        match fold_constants(statements).as_slice() {
+            [stmt::Stmt::Expression(expression)] => expression.to_string(),
+            _ => panic!("expected a single expression statement"),
+        }
+    }
+
+    #[test]
+    fn folds_arithmetic() {
+        assert_eq!(fold("1 + 2 * 3;"), "7");
+        assert_eq!(fold("(1 + 2) * 3;"), "9");
+        assert_eq!(fold("-(4 / 2);"), "-2");
+    }
+
+    #[test]
+    fn folds_unary() {
+        assert_eq!(fold("-5;"), "-5");
+        assert_eq!(fold("!true;"), "false");
+        assert_eq!(fold("!nil;"), "true");
+    }
+
+    #[test]
+    fn folds_comparisons_and_strings() {
+        assert_eq!(fold("1 < 2;"), "true");
+        assert_eq!(fold("\"foo\" + \"bar\";"), "foobar");
+        assert_eq!(fold("\"a\" == \"b\";"), "false");
+    }
+
+    #[test]
+    fn keeps_variables() {
+        assert_eq!(fold("x + (1 + 2);"), "(+ $x 3)");
+        assert_eq!(fold("x = 2 * 2;"), "(= $x, 4)");
+    }
+
+    #[test]
+    fn keeps_runtime_errors() {
+        assert_eq!(fold("1 + \"a\";"), "(+ 1 a)");
+        assert_eq!(fold("-\"a\";"), "(- a)");
+    }
+
+    fn without_dead_code(source: &str) -> Vec<stmt::Stmt> {
+        remove_dead_code(Parser::from_source(source).parse().0)
+    }
+
+    /// The statements of the block which is the body of the only statement, a loop.
+    fn loop_body(statements: &[stmt::Stmt]) -> &[stmt::Stmt] {
+        let [stmt::Stmt::Loop { body }] = statements else {
+            panic!("expected a loop");
+        };
+        let stmt::Stmt::Block(body) = body.as_ref() else {
+            panic!("expected a block");
+        };
+        body
+    }
+
+    #[test]
+    fn removes_statements_after_break_and_continue() {
+        let statements = without_dead_code("loop { x = 1; break; x = 2; x = 3; }");
+        let body = loop_body(&statements);
+        assert_eq!(body.len(), 2);
+        let stmt::Stmt::Expression(assignment) = &body[0] else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(assignment.to_string(), "(= $x, 1)");
+        assert!(matches!(body[1], stmt::Stmt::Break { label: None }));
+
+        let statements = without_dead_code("loop { { continue; x = 1; } x = 2; }");
+        let [stmt::Stmt::Block(inner), stmt::Stmt::Expression(_)] = loop_body(&statements) else {
+            panic!("expected a block and an expression statement");
+        };
+        assert!(matches!(
+            inner.as_slice(),
+            [stmt::Stmt::Continue { label: None }]
+        ));
+    }
+
+    #[test]
+    fn keeps_conditional_jumps() {
+        let statements = without_dead_code("loop { if (x) break; x = 1; }");
+        assert_eq!(loop_body(&statements).len(), 2);
+    }
+
+    #[test]
+    fn folds_inside_statements() {
+        let source = "{ if (1 < 2) x = 1 + 1; }";
+        let statements = fold_constants(Parser::from_source(source).parse().0);
+        let [stmt::Stmt::Block(block)] = statements.as_slice() else {
+            panic!("expected a block");
+        };
+        let [stmt::Stmt::If {
+            condition,
+            then_branch,
+            else_branch: None,
+        }] = block.as_slice()
+        else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(condition.to_string(), "true");
+        let stmt::Stmt::Expression(assignment) = then_branch.as_ref() else {
+            panic!("expected an expression statement");
+        };
+        assert_eq!(assignment.to_string(), "(= $x, 2)");
+    }
+}
@@ -0,0 +1,100 @@
+use std::cell::Cell;
+#[cfg(test)]
+use std::cell::RefCell;
+use std::io;
+use std::io::Write;
+use std::rc::Rc;
+
+/// Passes the first `limit` lines written through to `inner`, and drops the rest.
+pub struct LineLimitWriter<W: Write> {
+    inner: W,
+    limit: usize,
+    count: usize,
+    truncated: Rc<Cell<bool>>,
+}
+
+impl<W: Write> LineLimitWriter<W> {
+    pub fn new(inner: W, limit: usize) -> Self {
+        LineLimitWriter {
+            inner,
+            limit,
+            count: 0,
+            truncated: Rc::new(Cell::new(false)),
+        }
+    }
+
+    /// A flag set once output has been dropped, which can still be read after the writer is
+    /// handed over to an interpreter.
+    pub fn truncated(&self) -> Rc<Cell<bool>> {
+        self.truncated.clone()
+    }
+}
+
+impl<W: Write> Write for LineLimitWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut end = buf.len();
+        for (i, byte) in buf.iter().enumerate() {
+            if self.count == self.limit {
+                end = i;
+                break;
+            }
+            if *byte == b'\n' {
+                self.count += 1;
+            }
+        }
+        self.inner.write_all(&buf[..end])?;
+        if end < buf.len() {
+            self.truncated.set(true);
+        }
+        // the dropped bytes count as written, so that the program goes on
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A writer whose contents can still be read after it was handed to an interpreter, for tests.
+#[cfg(test)]
+#[derive(Clone, Default)]
+pub struct SharedOutput(pub Rc<RefCell<Vec<u8>>>);
+
+#[cfg(test)]
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lines_past_the_limit_are_dropped() {
+        let mut output = vec![];
+        let mut writer = LineLimitWriter::new(&mut output, 2);
+        let truncated = writer.truncated();
+        writer.write_all(b"one\ntw").unwrap();
+        assert!(!truncated.get());
+        writer.write_all(b"o\nthree\n").unwrap();
+        writer.write_all(b"four\n").unwrap();
+        assert!(truncated.get());
+        assert_eq!(output, b"one\ntwo\n");
+    }
+
+    #[test]
+    fn output_within_the_limit_is_not_truncated() {
+        let mut output = vec![];
+        let mut writer = LineLimitWriter::new(&mut output, 2);
+        writer.write_all(b"one\ntwo\n").unwrap();
+        writer.write_all(b"").unwrap();
+        assert!(!writer.truncated().get());
+        assert_eq!(output, b"one\ntwo\n");
+    }
+}
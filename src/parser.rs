@@ -1,47 +1,370 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::mem;
+use std::rc::Rc;
+
 use crate::expr;
-use crate::lox;
+use crate::intern;
 use crate::object;
+use crate::scanning;
 use crate::stmt;
 use crate::tokens;
 
-#[derive(Debug)]
-struct ParseError;
+/// An error found while parsing, at a token.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub line: usize,
+    /// Column (counted in characters, starting at 1) of the token at which the error is.
+    pub column: usize,
+    /// Where in the source the error is, like `'foo'` or `end`.
+    pub at: String,
+    pub message: String,
+}
+
+/// What kind of mistake a `ParseError` is.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParseErrorKind {
+    Invalid,
+    /// An expression statement at the end of the source without its `;`, which the REPL evaluates
+    /// as an expression instead.
+    UnterminatedExpression,
+}
+
+impl ParseError {
+    fn at_token(token: &tokens::Token, message: &str) -> ParseError {
+        let at = if token.token_type == tokens::TokenType::EOF {
+            token.token_type.to_string()
+        } else {
+            format!("'{}'", token.lexeme)
+        };
+        ParseError {
+            kind: ParseErrorKind::Invalid,
+            line: token.line,
+            column: token.column,
+            at,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// An error found in a program, either by the scanner or by the parser.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SyntaxError {
+    Scan(scanning::ScanError),
+    Parse(ParseError),
+}
+
+impl SyntaxError {
+    pub fn message(&self) -> &str {
+        match self {
+            SyntaxError::Scan(error) => &error.message,
+            SyntaxError::Parse(error) => &error.message,
+        }
+    }
+
+    /// The line and column of the error.
+    fn position(&self) -> (usize, usize) {
+        match self {
+            SyntaxError::Scan(error) => (error.line, error.column),
+            SyntaxError::Parse(error) => (error.line, error.column),
+        }
+    }
+}
+
+impl From<scanning::ScanError> for SyntaxError {
+    fn from(error: scanning::ScanError) -> Self {
+        SyntaxError::Scan(error)
+    }
+}
+
+impl From<ParseError> for SyntaxError {
+    fn from(error: ParseError) -> Self {
+        SyntaxError::Parse(error)
+    }
+}
+
+/// Something suspicious in a program which doesn't stop it from running, like an unused variable.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Warning {
+    pub line: usize,
+    /// Where in the source the warning is, like `'foo'`.
+    pub at: String,
+    pub message: String,
+}
+
+/// A line typed in the REPL, as parsed by `Parser::parse_repl_line`.
+pub enum ReplLine {
+    Statements(Vec<stmt::Stmt>, Vec<Warning>),
+    /// An expression without a semicolon, whose value is shown.
+    Expression(expr::Expr),
+    Empty,
+    Error(Vec<SyntaxError>),
+}
+
+// binary operators of each precedence level, from the lowest to the highest
+const EQUALITY: &[expr::BinaryOperator] = &[
+    expr::BinaryOperator::BangEqual,
+    expr::BinaryOperator::EqualEqual,
+];
+const COMPARISON: &[expr::BinaryOperator] = &[
+    expr::BinaryOperator::GreaterThan,
+    expr::BinaryOperator::GreaterEqualThan,
+    expr::BinaryOperator::LessThan,
+    expr::BinaryOperator::LessEqualThan,
+];
+const BIT_OR: &[expr::BinaryOperator] = &[expr::BinaryOperator::BitOr];
+const BIT_XOR: &[expr::BinaryOperator] = &[expr::BinaryOperator::BitXor];
+const BIT_AND: &[expr::BinaryOperator] = &[expr::BinaryOperator::BitAnd];
+const SHIFT: &[expr::BinaryOperator] = &[expr::BinaryOperator::Shl, expr::BinaryOperator::Shr];
+const TERM: &[expr::BinaryOperator] = &[expr::BinaryOperator::Sub, expr::BinaryOperator::Add];
+const FACTOR: &[expr::BinaryOperator] = &[expr::BinaryOperator::Mul, expr::BinaryOperator::Div];
+
+/// A local variable, as seen by the parser.
+struct LocalVariable {
+    line: usize,
+    used: bool,
+    constant: bool,
+}
+
+/// The tokens left to parse, with the current one and the one after it buffered.
+pub struct TokenStream<'a> {
+    tokens: prefetch::Prefetched<Box<dyn Iterator<Item = tokens::Token<'a>> + 'a>, 2>,
+    /// The last token consumed.
+    previous: Option<tokens::Token<'a>>,
+}
+
+impl<'a> TokenStream<'a> {
+    /// Wraps `tokens`, which must end with an `EOF` token.
+    pub fn new(tokens: impl Iterator<Item = tokens::Token<'a>> + 'a) -> TokenStream<'a> {
+        TokenStream {
+            tokens: prefetch::Prefetched::new(Box::new(tokens)),
+            previous: None,
+        }
+    }
+
+    /// The current token, which is the next to be consumed.
+    pub fn peek(&self) -> &tokens::Token<'a> {
+        self.tokens.peek().expect("tokens end with EOF")
+    }
+
+    /// The token after the current one, if there is one.
+    pub fn peek2(&self) -> Option<&tokens::Token<'a>> {
+        self.tokens.peek_nth(1)
+    }
+
+    /// The last token consumed.
+    pub fn previous(&self) -> &tokens::Token<'a> {
+        self.previous.as_ref().expect("no token consumed yet")
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.peek().token_type == tokens::TokenType::EOF
+    }
+
+    /// Consumes the current token and returns it. The `EOF` token is never consumed.
+    pub fn advance(&mut self) -> &tokens::Token<'a> {
+        if !self.is_at_end() {
+            self.previous = self.tokens.next();
+        }
+        self.previous()
+    }
+
+    /// Consumes the current token if it is of `token_type`, or fails with `message`.
+    pub fn consume(
+        &mut self,
+        token_type: tokens::TokenType,
+        message: &str,
+    ) -> Result<&tokens::Token<'a>, ParseError> {
+        if self.peek().token_type == token_type {
+            Ok(self.advance())
+        } else {
+            Err(ParseError::at_token(self.peek(), message))
+        }
+    }
+
+    /// Consumes the current token if it is of `token_type`.
+    pub fn match_token(&mut self, token_type: tokens::TokenType) -> bool {
+        let is_match = self.peek().token_type == token_type;
+        if is_match {
+            self.advance();
+        }
+        is_match
+    }
+}
 
 pub struct Parser<'a> {
-    tokens: Vec<tokens::Token<'a>>,
-    current: usize,
+    /// The source the tokens were scanned from.
+    source: &'a str,
+    /// The tokens left to parse, starting with the current one.
+    tokens: TokenStream<'a>,
+    /// Labels of the loops enclosing the statement being parsed, innermost last.
+    loop_labels: Vec<Option<Rc<str>>>,
+    /// Variables declared in each enclosing local scope, innermost last.
+    local_scopes: Vec<HashMap<Rc<str>, LocalVariable>>,
+    /// Global constants declared so far, which are forgotten if a variable replaces them.
+    global_constants: HashSet<Rc<str>>,
+    /// Errors found so far, including those that didn't stop a statement from being parsed.
+    errors: Vec<ParseError>,
+    /// Errors the scanner found in the tokens pulled so far.
+    scan_errors: Rc<RefCell<Vec<scanning::ScanError>>>,
+    warnings: Vec<Warning>,
 }
 
-impl Parser<'_> {
-    pub fn new(tokens: Vec<tokens::Token>) -> Parser {
-        Parser { tokens, current: 0 }
+impl<'a> Parser<'a> {
+    /// Scans and parses `source`.
+    pub fn from_source(source: &'a str) -> Parser<'a> {
+        Parser::new(source, scanning::Scanner::new(source).into_token_iter())
+    }
+
+    /// Parses tokens of `source` as they are scanned. Scanner errors are returned along with the
+    /// parser's.
+    pub fn new(
+        source: &'a str,
+        tokens: impl Iterator<Item = Result<tokens::Token<'a>, scanning::ScanError>> + 'a,
+    ) -> Parser<'a> {
+        let scan_errors = Rc::new(RefCell::new(vec![]));
+        let found = scan_errors.clone();
+        let tokens = tokens.filter_map(move |scanned| match scanned {
+            Ok(token) => Some(token),
+            Err(error) => {
+                found.borrow_mut().push(error);
+                None
+            }
+        });
+        Parser {
+            source,
+            tokens: TokenStream::new(tokens),
+            loop_labels: vec![],
+            local_scopes: vec![],
+            global_constants: HashSet::new(),
+            errors: vec![],
+            scan_errors,
+            warnings: vec![],
+        }
     }
 
-    pub fn parse(mut self) -> Vec<stmt::Stmt> {
+    /// Parses a whole program, returning the statements that could be parsed, the errors and the
+    /// warnings.
+    pub fn parse(mut self) -> (Vec<stmt::Stmt>, Vec<SyntaxError>, Vec<Warning>) {
         let mut statements = vec![];
-        while !self.is_at_end() {
-            if let Ok(statement) = self.declaration() {
-                statements.push(statement)
+        while !self.tokens.is_at_end() {
+            match self.declaration() {
+                Ok(statement) => statements.push(statement),
+                Err(error) => self.errors.push(error),
             }
         }
-        return statements;
+        let warnings = mem::take(&mut self.warnings);
+        (statements, self.into_errors(), warnings)
     }
 
-    fn statement(&mut self) -> Result<stmt::Stmt, ParseError> {
-        if self.match_token(tokens::TokenType::Print) {
-            return self.print_statement();
+    /// The errors of the scanner and of the parser, in the order they appear in the source.
+    fn into_errors(self) -> Vec<SyntaxError> {
+        let mut errors: Vec<SyntaxError> = self
+            .scan_errors
+            .take()
+            .into_iter()
+            .map(SyntaxError::from)
+            .collect();
+        errors.extend(self.errors.into_iter().map(SyntaxError::from));
+        // the sort is stable, so errors at the same position keep their order
+        errors.sort_by_key(SyntaxError::position);
+        errors
+    }
+
+    /// Parses the tokens of a line typed in the REPL, which is either statements or a single
+    /// expression without a semicolon.
+    ///
+    /// The line is parsed as statements first, and as an expression only if that fails for the
+    /// lack of a semicolon at the very end.
+    pub fn parse_repl_line(line: &'a str, tokens: Vec<tokens::Token<'a>>) -> ReplLine {
+        if tokens
+            .iter()
+            .all(|token| token.token_type == tokens::TokenType::EOF)
+        {
+            return ReplLine::Empty;
         }
-        if self.match_token(tokens::TokenType::LeftBrace) {
+        let (statements, errors, warnings) =
+            Parser::new(line, tokens.clone().into_iter().map(Ok)).parse();
+        match errors.as_slice() {
+            [] => ReplLine::Statements(statements, warnings),
+            [SyntaxError::Parse(error)] if error.kind == ParseErrorKind::UnterminatedExpression => {
+                match Parser::new(line, tokens.into_iter().map(Ok)).parse_expression_only() {
+                    Ok(expression) => ReplLine::Expression(expression),
+                    Err(_) => ReplLine::Error(errors),
+                }
+            }
+            _ => ReplLine::Error(errors),
+        }
+    }
+
+    /// Scans and parses `source` as a single expression, returning the errors instead of reporting
+    /// them.
+    pub fn parse_expression(source: &str) -> Result<expr::Expr, Vec<SyntaxError>> {
+        Parser::from_source(source).parse_expression_only()
+    }
+
+    /// Parses a single expression that must span all of the tokens.
+    pub fn parse_expression_only(mut self) -> Result<expr::Expr, Vec<SyntaxError>> {
+        let expression = match self.expression() {
+            Ok(_) if !self.tokens.is_at_end() => {
+                let error = self.error("Expect end of expression.");
+                self.errors.push(error);
+                None
+            }
+            Ok(expression) => Some(expression),
+            Err(error) => {
+                self.errors.push(error);
+                None
+            }
+        };
+        let errors = self.into_errors();
+        match expression {
+            Some(expression) if errors.is_empty() => Ok(expression),
+            _ => Err(errors),
+        }
+    }
+
+    fn statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        if self.tokens.match_token(tokens::TokenType::LeftBrace) {
             return self.block();
         }
-        if self.match_token(tokens::TokenType::If) {
+        if self.tokens.match_token(tokens::TokenType::If) {
             return self.if_statement();
         }
-        if self.match_token(tokens::TokenType::While) {
-            return self.while_statement();
+        if self.tokens.match_token(tokens::TokenType::While) {
+            return self.while_statement(None);
         }
-        if self.match_token(tokens::TokenType::For) {
-            return self.for_statement();
+        if self.tokens.match_token(tokens::TokenType::For) {
+            return self.for_statement(None);
+        }
+        if self.tokens.match_token(tokens::TokenType::Loop) {
+            return self.loop_statement(None);
+        }
+        if self.tokens.match_token(tokens::TokenType::Repeat) {
+            return self.repeat_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Break) {
+            return self.break_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Continue) {
+            return self.continue_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Try) {
+            return self.try_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Throw) {
+            return self.throw_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Assert) {
+            return self.assert_statement();
+        }
+        if self.tokens.match_token(tokens::TokenType::Import) {
+            return self.import_statement();
+        }
+        if let Some(label) = self.match_label() {
+            return self.labeled_statement(label);
         }
         self.expression_statement()
     }
@@ -52,19 +375,43 @@ impl Parser<'_> {
 
     fn assignment(&mut self) -> Result<expr::Expr, ParseError> {
         let expr = self.or()?;
-        if self.match_token(tokens::TokenType::Equal) {
+        if self.tokens.match_token(tokens::TokenType::Equal) {
+            if let expr::Expr::Variable(name) = &expr {
+                if self.is_constant(name) {
+                    let message = format!("Can't assign to constant '{}'.", name);
+                    self.errors.push(self.error_at_previous(&message));
+                }
+            }
             let value = self.assignment()?;
             if let expr::Expr::Variable(name) = expr {
                 return Ok(expr::Expr::Assign(name, Box::new(value)));
             }
-            self.error("Invalid assignment target.");
+            self.errors.push(self.error("Invalid assignment target."));
         }
         Ok(expr)
     }
 
     fn equality(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut lhs = self.membership()?;
+        while let Some(operator) = self.match_binary(EQUALITY) {
+            let rhs = self.membership()?;
+            lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn membership(&mut self) -> Result<expr::Expr, ParseError> {
         let mut lhs = self.comparison()?;
-        while let Some(operator) = self.match_fn(translate_equality) {
+        loop {
+            let operator = if self.tokens.match_token(tokens::TokenType::In) {
+                expr::BinaryOperator::In
+            } else if self.tokens.match_token(tokens::TokenType::Not) {
+                self.tokens
+                    .consume(tokens::TokenType::In, "Expect 'in' after 'not'.")?;
+                expr::BinaryOperator::NotIn
+            } else {
+                break;
+            };
             let rhs = self.comparison()?;
             lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
         }
@@ -72,8 +419,45 @@ impl Parser<'_> {
     }
 
     fn comparison(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut lhs = self.bit_or()?;
+        while let Some(operator) = self.match_binary(COMPARISON) {
+            let rhs = self.bit_or()?;
+            lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // like in Rust, bitwise operators bind tighter than comparisons, so `5 & 3 == 1` holds
+    fn bit_or(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut lhs = self.bit_xor()?;
+        while let Some(operator) = self.match_binary(BIT_OR) {
+            let rhs = self.bit_xor()?;
+            lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn bit_xor(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut lhs = self.bit_and()?;
+        while let Some(operator) = self.match_binary(BIT_XOR) {
+            let rhs = self.bit_and()?;
+            lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn bit_and(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut lhs = self.shift()?;
+        while let Some(operator) = self.match_binary(BIT_AND) {
+            let rhs = self.shift()?;
+            lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn shift(&mut self) -> Result<expr::Expr, ParseError> {
         let mut lhs = self.term()?;
-        while let Some(operator) = self.match_fn(translate_comparison) {
+        while let Some(operator) = self.match_binary(SHIFT) {
             let rhs = self.term()?;
             lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
         }
@@ -82,7 +466,7 @@ impl Parser<'_> {
 
     fn term(&mut self) -> Result<expr::Expr, ParseError> {
         let mut lhs = self.factor()?;
-        while let Some(operator) = self.match_fn(translate_term) {
+        while let Some(operator) = self.match_binary(TERM) {
             let rhs = self.factor()?;
             lhs = expr::Expr::Binary(Box::new(lhs), operator, Box::new(rhs));
         }
@@ -91,7 +475,7 @@ impl Parser<'_> {
 
     fn factor(&mut self) -> Result<expr::Expr, ParseError> {
         let mut acc = self.unary()?;
-        while let Some(operator) = self.match_fn(translate_factor) {
+        while let Some(operator) = self.match_binary(FACTOR) {
             let next = self.unary()?;
             acc = expr::Expr::Binary(Box::new(acc), operator, Box::new(next));
         }
@@ -99,7 +483,7 @@ impl Parser<'_> {
     }
 
     fn unary(&mut self) -> Result<expr::Expr, ParseError> {
-        if let Some(operator) = self.match_fn(translate_unary) {
+        if let Some(operator) = self.match_fn(|token| expr::UnaryOperator::try_from(token).ok()) {
             Ok(expr::Expr::Unary(operator, Box::new(self.unary()?)))
         } else {
             self.call()
@@ -110,99 +494,95 @@ impl Parser<'_> {
         if let Some(literal) = self.match_fn(translate_literal) {
             return Ok(expr::Expr::Literal(literal));
         }
-        if self.match_token(tokens::TokenType::LeftParen) {
+        if let tokens::TokenType::Interpolation(_) = self.tokens.peek().token_type {
+            return self.interpolation();
+        }
+        if self.tokens.match_token(tokens::TokenType::LeftParen) {
             let expression = self.expression()?;
-            self.consume(
+            if self.tokens.match_token(tokens::TokenType::Comma) {
+                return self.tuple(expression);
+            }
+            self.tokens.consume(
                 tokens::TokenType::RightParen,
                 "Expected ')' after expression",
             )?;
             return Ok(expr::Expr::Grouping(Box::new(expression)));
         }
         if let Some(name) = self.match_identifier() {
+            // a global of a module imported with an alias, like `module.name`
+            if self.tokens.match_token(tokens::TokenType::Dot) {
+                let Some(member) = self.match_identifier() else {
+                    return Err(self.error("Expect name after '.'."));
+                };
+                let name = intern::intern(&format!("{}.{}", name, member));
+                return Ok(expr::Expr::Variable(name));
+            }
+            let mut scopes = self.local_scopes.iter_mut().rev();
+            if let Some(variable) = scopes.find_map(|scope| scope.get_mut(&name)) {
+                variable.used = true;
+            }
             return Ok(expr::Expr::Variable(name));
         }
         Err(self.error("Expected expression."))
     }
 
-    fn advance(&mut self) -> &tokens::Token {
-        if !self.is_at_end() {
-            self.current += 1;
-        }
-        self.previous()
-    }
-
-    fn current(&self) -> &tokens::Token {
-        &self.tokens[self.current]
-    }
-
-    fn previous(&self) -> &tokens::Token {
-        &self.tokens[self.current - 1]
-    }
-
-    fn consume(
-        &mut self,
-        token_type: tokens::TokenType,
-        error_message: &str,
-    ) -> Result<&tokens::Token, ParseError> {
-        if self.current().token_type == token_type {
-            Ok(self.advance())
-        } else {
-            Err(self.error(error_message))
-        }
+    fn error(&self, message: &str) -> ParseError {
+        ParseError::at_token(self.tokens.peek(), message)
     }
 
-    fn error(&self, message: &str) -> ParseError {
-        lox::error_from_token(self.current(), message);
-        ParseError {}
+    fn error_at_previous(&self, message: &str) -> ParseError {
+        ParseError::at_token(self.tokens.previous(), message)
     }
 
     fn synchronize(&mut self) {
-        self.advance(); // consume problematic token
+        self.tokens.advance(); // consume problematic token
 
-        while !self.is_at_end() {
-            if self.previous().token_type == tokens::TokenType::Semicolon {
+        while !self.tokens.is_at_end() {
+            if self.tokens.previous().token_type == tokens::TokenType::Semicolon {
                 return;
             }
 
-            match self.current().token_type {
+            match self.tokens.peek().token_type {
                 tokens::TokenType::Class
                 | tokens::TokenType::Fun
                 | tokens::TokenType::Var
+                | tokens::TokenType::Const
                 | tokens::TokenType::For
                 | tokens::TokenType::If
                 | tokens::TokenType::While
-                | tokens::TokenType::Print
+                | tokens::TokenType::Loop
+                | tokens::TokenType::Repeat
+                | tokens::TokenType::Import
+                | tokens::TokenType::Export
+                | tokens::TokenType::Assert
                 | tokens::TokenType::Return => return,
-                _ => self.advance(),
+                _ => self.tokens.advance(),
             };
         }
     }
 
-    fn is_at_end(&self) -> bool {
-        self.current().token_type == tokens::TokenType::EOF
-    }
-
     fn expression_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
         let value = self.expression()?;
-        self.consume(
+        if self.tokens.is_at_end() {
+            return Err(ParseError {
+                kind: ParseErrorKind::UnterminatedExpression,
+                ..self.error("Expected ';' after expression")
+            });
+        }
+        self.tokens.consume(
             tokens::TokenType::Semicolon,
             "Expected ';' after expression",
         )?;
         Ok(stmt::Stmt::Expression(value))
     }
 
-    fn print_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
-        let value = self.expression()?;
-        self.consume(
-            tokens::TokenType::Semicolon,
-            "Expected ';' after expression",
-        )?;
-        Ok(stmt::Stmt::Print(value))
-    }
-
     fn declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
-        let maybe_declaration = if self.match_token(tokens::TokenType::Var) {
+        let maybe_declaration = if self.tokens.match_token(tokens::TokenType::Export) {
+            self.export_declaration()
+        } else if self.tokens.match_token(tokens::TokenType::Var) {
             self.var_declaration()
+        } else if self.tokens.match_token(tokens::TokenType::Const) {
+            self.const_declaration()
         } else {
             self.statement()
         };
@@ -212,38 +592,138 @@ impl Parser<'_> {
         maybe_declaration
     }
 
-    fn var_declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
+    /// Parses the name of a declared variable and adds it to the current scope.
+    fn declare(&mut self, constant: bool) -> Result<Rc<str>, ParseError> {
         let name = self
             .match_identifier()
             .ok_or_else(|| self.error("Expect variable name."))?;
-        let initializer = if self.match_token(tokens::TokenType::Equal) {
+        // globals can be redefined, which is handy in the REPL
+        let line = self.tokens.previous().line;
+        if let Some(scope) = self.local_scopes.last_mut() {
+            let variable = LocalVariable {
+                line,
+                used: false,
+                constant,
+            };
+            if scope.insert(name.clone(), variable).is_some() {
+                let message = format!("Already a variable with the name '{}' in this scope.", name);
+                self.errors.push(self.error_at_previous(&message));
+            }
+        } else if constant {
+            self.global_constants.insert(name.clone());
+        } else {
+            self.global_constants.remove(&name);
+        }
+        Ok(name)
+    }
+
+    /// Whether `name` refers to a constant, in the innermost scope declaring it.
+    fn is_constant(&self, name: &Rc<str>) -> bool {
+        let mut scopes = self.local_scopes.iter().rev();
+        match scopes.find_map(|scope| scope.get(name)) {
+            Some(variable) => variable.constant,
+            None => self.global_constants.contains(name),
+        }
+    }
+
+    fn var_declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let name = self.declare(false)?;
+        let initializer = if self.tokens.match_token(tokens::TokenType::Equal) {
             Some(self.expression()?)
         } else {
             None
         };
-        self.consume(
+        self.tokens.consume(
             tokens::TokenType::Semicolon,
             "Expect ';' after variable declaration.",
         )?;
         Ok(stmt::Stmt::Var { name, initializer })
     }
 
+    fn const_declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let name = self.declare(true)?;
+        self.tokens
+            .consume(tokens::TokenType::Equal, "Expect '=' after constant name.")?;
+        let initializer = self.expression()?;
+        self.tokens.consume(
+            tokens::TokenType::Semicolon,
+            "Expect ';' after constant declaration.",
+        )?;
+        Ok(stmt::Stmt::Const { name, initializer })
+    }
+
+    fn export_declaration(&mut self) -> Result<stmt::Stmt, ParseError> {
+        if !self.local_scopes.is_empty() {
+            return Err(self.error_at_previous("Can only export top-level declarations."));
+        }
+        let declaration = if self.tokens.match_token(tokens::TokenType::Var) {
+            self.var_declaration()?
+        } else if self.tokens.match_token(tokens::TokenType::Const) {
+            self.const_declaration()?
+        } else {
+            return Err(self.error("Expect declaration after 'export'."));
+        };
+        Ok(stmt::Stmt::Export(Box::new(declaration)))
+    }
+
+    /// Parses the statements of a block, warning about the first one after a `break` or
+    /// `continue`, which can never run.
     fn block(&mut self) -> Result<stmt::Stmt, ParseError> {
-        let mut statements = vec![];
-        while self.current().token_type != tokens::TokenType::RightBrace && !self.is_at_end() {
-            statements.push(self.declaration()?)
+        self.in_scope(|parser| {
+            let mut statements: Vec<stmt::Stmt> = vec![];
+            while parser.tokens.peek().token_type != tokens::TokenType::RightBrace
+                && !parser.tokens.is_at_end()
+            {
+                if let [.., stmt::Stmt::Break { .. } | stmt::Stmt::Continue { .. }] =
+                    statements.as_slice()
+                {
+                    let token = parser.tokens.peek();
+                    parser.warnings.push(Warning {
+                        line: token.line,
+                        at: format!("'{}'", token.lexeme),
+                        message: "Unreachable code.".to_string(),
+                    });
+                }
+                statements.push(parser.declaration()?)
+            }
+            parser
+                .tokens
+                .consume(tokens::TokenType::RightBrace, "Expected '}' after block.")?;
+            Ok(stmt::Stmt::Block(statements))
+        })
+    }
+
+    /// Parses with `parse` in a new local scope, matching a block at runtime.
+    ///
+    /// Adds a warning for each variable of the scope that was never read.
+    fn in_scope<T>(&mut self, parse: impl FnOnce(&mut Self) -> T) -> T {
+        self.local_scopes.push(HashMap::new());
+        let result = parse(self);
+        let scope = self.local_scopes.pop().unwrap_or_default();
+        let mut unused: Vec<_> = scope
+            .into_iter()
+            .filter(|(_, variable)| !variable.used)
+            .collect();
+        unused.sort_by_key(|(name, variable)| (variable.line, name.clone()));
+        for (name, variable) in unused {
+            self.warnings.push(Warning {
+                line: variable.line,
+                at: format!("'{}'", name),
+                message: format!("Local variable '{}' is never used.", name),
+            });
         }
-        self.consume(tokens::TokenType::RightBrace, "Expected '}' after block.")?;
-        Ok(stmt::Stmt::Block(statements))
+        result
     }
 
     fn if_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
-        self.consume(tokens::TokenType::LeftParen, "Expect '(' after if.")?;
+        self.tokens
+            .consume(tokens::TokenType::LeftParen, "Expect '(' after if.")?;
         let condition = self.expression()?;
-        self.consume(tokens::TokenType::RightParen, "Expect ')' after if.")?;
+        self.tokens
+            .consume(tokens::TokenType::RightParen, "Expect ')' after if.")?;
 
         let then_branch = self.statement()?;
-        let else_branch = if self.match_token(tokens::TokenType::Else) {
+        let else_branch = if self.tokens.match_token(tokens::TokenType::Else) {
             Some(self.statement()?)
         } else {
             None
@@ -258,7 +738,7 @@ impl Parser<'_> {
 
     fn or(&mut self) -> Result<expr::Expr, ParseError> {
         let mut expr = self.and()?;
-        while self.match_token(tokens::TokenType::Or) {
+        while self.tokens.match_token(tokens::TokenType::Or) {
             expr = expr::Expr::Logical(
                 Box::new(expr),
                 expr::LogicalOperator::Or,
@@ -270,7 +750,7 @@ impl Parser<'_> {
 
     fn and(&mut self) -> Result<expr::Expr, ParseError> {
         let mut expr = self.equality()?;
-        while self.match_token(tokens::TokenType::And) {
+        while self.tokens.match_token(tokens::TokenType::And) {
             expr = expr::Expr::Logical(
                 Box::new(expr),
                 expr::LogicalOperator::And,
@@ -280,48 +760,230 @@ impl Parser<'_> {
         Ok(expr)
     }
 
-    fn while_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
-        self.consume(tokens::TokenType::LeftParen, "Expect '(' after 'while'.")?;
+    fn while_statement(&mut self, label: Option<Rc<str>>) -> Result<stmt::Stmt, ParseError> {
+        self.tokens
+            .consume(tokens::TokenType::LeftParen, "Expect '(' after 'while'.")?;
         let condition = self.expression()?;
-        self.consume(tokens::TokenType::RightParen, "Expect ')' after 'while'.")?;
-        let body = self.statement()?;
-        Ok(stmt::Stmt::While(condition, Box::new(body)))
+        self.tokens
+            .consume(tokens::TokenType::RightParen, "Expect ')' after 'while'.")?;
+        let body = self.loop_body(label.clone())?;
+        Ok(make_loop(label, condition, None, body))
     }
 
-    fn for_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
-        self.consume(tokens::TokenType::LeftParen, "Expect '(' after 'for'.")?;
+    fn loop_statement(&mut self, label: Option<Rc<str>>) -> Result<stmt::Stmt, ParseError> {
+        if self.tokens.peek().token_type != tokens::TokenType::LeftBrace {
+            return Err(self.error("Expect '{' after 'loop'."));
+        }
+        let body = self.loop_body(label.clone())?;
+        if label.is_some() {
+            // `Loop` has no label, so a labelled loop is a labelled `while (true)`
+            let condition = expr::Expr::Literal(object::LoxObject::True);
+            return Ok(make_loop(label, condition, None, body));
+        }
+        Ok(stmt::Stmt::Loop {
+            body: Box::new(body),
+        })
+    }
 
-        let initializer = if self.match_token(tokens::TokenType::Semicolon) {
+    fn repeat_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let count = self.expression()?;
+        if self.tokens.peek().token_type != tokens::TokenType::LeftBrace {
+            return Err(self.error("Expect '{' before repeat body."));
+        }
+        let body = self.loop_body(None)?;
+        Ok(stmt::Stmt::Repeat {
+            count,
+            body: Box::new(body),
+        })
+    }
+
+    /// Parses the body of a loop, in which `break` and `continue` statements are allowed.
+    fn loop_body(&mut self, label: Option<Rc<str>>) -> Result<stmt::Stmt, ParseError> {
+        self.loop_labels.push(label);
+        let body = self.statement();
+        self.loop_labels.pop();
+        body
+    }
+
+    /// Parses the loop following a `label:` prefix.
+    fn labeled_statement(&mut self, label: Rc<str>) -> Result<stmt::Stmt, ParseError> {
+        if self.tokens.match_token(tokens::TokenType::While) {
+            self.while_statement(Some(label))
+        } else if self.tokens.match_token(tokens::TokenType::For) {
+            self.for_statement(Some(label))
+        } else if self.tokens.match_token(tokens::TokenType::Loop) {
+            self.loop_statement(Some(label))
+        } else {
+            Err(self.error("Expect loop after label."))
+        }
+    }
+
+    fn break_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let label = self.loop_jump_label("break")?;
+        self.tokens
+            .consume(tokens::TokenType::Semicolon, "Expect ';' after 'break'.")?;
+        Ok(stmt::Stmt::Break { label })
+    }
+
+    fn continue_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let label = self.loop_jump_label("continue")?;
+        self.tokens
+            .consume(tokens::TokenType::Semicolon, "Expect ';' after 'continue'.")?;
+        Ok(stmt::Stmt::Continue { label })
+    }
+
+    /// Parses the optional label after `break` or `continue`, which must be an enclosing loop's.
+    fn loop_jump_label(&mut self, keyword: &str) -> Result<Option<Rc<str>>, ParseError> {
+        if self.loop_labels.is_empty() {
+            let message = format!("Can't use '{}' outside of a loop.", keyword);
+            return Err(self.error_at_previous(&message));
+        }
+        let label = self.match_identifier();
+        if let Some(name) = &label {
+            if !self.loop_labels.iter().flatten().any(|label| label == name) {
+                return Err(self.error_at_previous("No enclosing loop with this label."));
+            }
+        }
+        Ok(label)
+    }
+
+    fn try_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        self.tokens
+            .consume(tokens::TokenType::LeftBrace, "Expect '{' after 'try'.")?;
+        let body = self.block()?;
+
+        self.tokens
+            .consume(tokens::TokenType::Catch, "Expect 'catch' after try block.")?;
+        self.tokens
+            .consume(tokens::TokenType::LeftParen, "Expect '(' after 'catch'.")?;
+        let catch_var = self
+            .match_identifier()
+            .ok_or_else(|| self.error("Expect exception variable name."))?;
+        self.tokens.consume(
+            tokens::TokenType::RightParen,
+            "Expect ')' after exception variable.",
+        )?;
+        self.tokens.consume(
+            tokens::TokenType::LeftBrace,
+            "Expect '{' after catch clause.",
+        )?;
+        let catch_body = self.block()?;
+
+        let finally_body = if self.tokens.match_token(tokens::TokenType::Finally) {
+            self.tokens
+                .consume(tokens::TokenType::LeftBrace, "Expect '{' after 'finally'.")?;
+            Some(Box::new(self.block()?))
+        } else {
             None
-        } else if self.match_token(tokens::TokenType::Var) {
+        };
+
+        Ok(stmt::Stmt::TryCatch {
+            body: Box::new(body),
+            catch_var,
+            catch_body: Box::new(catch_body),
+            finally_body,
+        })
+    }
+
+    fn throw_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let value = self.expression()?;
+        self.tokens.consume(
+            tokens::TokenType::Semicolon,
+            "Expect ';' after thrown value.",
+        )?;
+        Ok(stmt::Stmt::Throw(value))
+    }
+
+    fn assert_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let start = self.tokens.peek().offset;
+        let condition = self.expression()?;
+        let end = self.tokens.previous().offset + self.tokens.previous().lexeme.len();
+        let source = self.source[start..end].to_string();
+        // `assert(condition, message)` would otherwise assert a tuple, which is always truthy
+        if let expr::Expr::Tuple(_) = condition {
+            return Err(
+                self.error_at_previous("Assert takes `condition, message` without parentheses.")
+            );
+        }
+        let message = if self.tokens.match_token(tokens::TokenType::Comma) {
+            Some(self.expression()?)
+        } else {
+            None
+        };
+        self.tokens
+            .consume(tokens::TokenType::Semicolon, "Expect ';' after assertion.")?;
+        Ok(stmt::Stmt::Assert {
+            condition,
+            message,
+            source,
+        })
+    }
+
+    fn import_statement(&mut self) -> Result<stmt::Stmt, ParseError> {
+        let path = match self.tokens.peek().token_type {
+            tokens::TokenType::String(path) => path.to_string(),
+            _ => return Err(self.error("Expect module path after 'import'.")),
+        };
+        self.tokens.advance();
+        // `as` is only a keyword here, so that it can still name variables
+        let alias = match &self.tokens.peek().token_type {
+            tokens::TokenType::Identifier("as") => {
+                self.tokens.advance();
+                let alias = self.match_identifier();
+                Some(alias.ok_or_else(|| self.error("Expect module alias after 'as'."))?)
+            }
+            _ => None,
+        };
+        self.tokens.consume(
+            tokens::TokenType::Semicolon,
+            "Expect ';' after module path.",
+        )?;
+        Ok(stmt::Stmt::Import { path, alias })
+    }
+
+    fn for_statement(&mut self, label: Option<Rc<str>>) -> Result<stmt::Stmt, ParseError> {
+        // the initializer is in a block of its own, see the end of `for_clauses`
+        self.in_scope(|parser| parser.for_clauses(label))
+    }
+
+    fn for_clauses(&mut self, label: Option<Rc<str>>) -> Result<stmt::Stmt, ParseError> {
+        self.tokens
+            .consume(tokens::TokenType::LeftParen, "Expect '(' after 'for'.")?;
+
+        let initializer = if self.tokens.match_token(tokens::TokenType::Semicolon) {
+            None
+        } else if self.tokens.match_token(tokens::TokenType::Var) {
             Some(self.var_declaration()?)
         } else {
             Some(self.expression_statement()?)
         };
 
-        let condition = if self.current().token_type == tokens::TokenType::Semicolon {
+        let condition = if self.tokens.peek().token_type == tokens::TokenType::Semicolon {
             None
         } else {
             Some(self.expression()?)
         };
-        self.consume(tokens::TokenType::Semicolon, "Expect ';' after condition.")?;
+        self.tokens
+            .consume(tokens::TokenType::Semicolon, "Expect ';' after condition.")?;
 
-        let increment = if self.current().token_type == tokens::TokenType::RightParen {
+        if self.tokens.peek().token_type == tokens::TokenType::Var {
+            return Err(self.error("Variable declaration not allowed in for loop increment."));
+        }
+        let increment = if self.tokens.peek().token_type == tokens::TokenType::RightParen {
             None
         } else {
             Some(self.expression()?)
         };
-        self.consume(tokens::TokenType::RightParen, "Expect ')' after increment.")?;
+        self.tokens
+            .consume(tokens::TokenType::RightParen, "Expect ')' after increment.")?;
 
-        let mut body = self.statement()?;
+        let mut body = self.loop_body(label.clone())?;
 
-        if let Some(expression) = increment {
-            body = stmt::Stmt::Block(vec![body, stmt::Stmt::Expression(expression)])
-        }
-
-        body = stmt::Stmt::While(
+        body = make_loop(
+            label,
             condition.unwrap_or(expr::Expr::Literal(object::LoxObject::True)),
-            Box::new(body),
+            increment,
+            body,
         );
 
         if let Some(statement) = initializer {
@@ -331,40 +993,105 @@ impl Parser<'_> {
         Ok(body)
     }
 
-    fn match_token(&mut self, token_type: tokens::TokenType) -> bool {
-        let is_match = self.current().token_type == token_type;
-        if is_match {
-            self.advance();
+    fn match_identifier(&mut self) -> Option<Rc<str>> {
+        if let tokens::TokenType::Identifier(s) = &self.tokens.peek().token_type {
+            let some_string = Some(intern::intern(s));
+            self.tokens.advance();
+            some_string
+        } else {
+            None
         }
-        return is_match;
     }
 
-    fn match_identifier(&mut self) -> Option<String> {
-        if let tokens::TokenType::Identifier(s) = &self.current().token_type {
-            let some_string = Some(s.to_string());
-            self.advance();
-            some_string
+    /// Matches a loop label, an identifier followed by a colon.
+    fn match_label(&mut self) -> Option<Rc<str>> {
+        let is_label = matches!(
+            self.tokens.peek().token_type,
+            tokens::TokenType::Identifier(_)
+        ) && self
+            .tokens
+            .peek2()
+            .is_some_and(|token| token.token_type == tokens::TokenType::Colon);
+        if is_label {
+            let label = self.match_identifier();
+            self.tokens.advance(); // consume ':'
+            label
         } else {
             None
         }
     }
 
+    /// Matches one of the binary `operators`, which are those of a single precedence level.
+    fn match_binary(&mut self, operators: &[expr::BinaryOperator]) -> Option<expr::BinaryOperator> {
+        self.match_fn(|token| {
+            expr::BinaryOperator::try_from(token)
+                .ok()
+                .filter(|operator| operators.contains(operator))
+        })
+    }
+
     fn match_fn<T, F>(&mut self, translate: F) -> Option<T>
     where
         F: Fn(&tokens::TokenType) -> Option<T>,
     {
-        let translated = translate(&self.current().token_type);
+        let translated = translate(&self.tokens.peek().token_type);
         if translated.is_some() {
-            self.advance();
+            self.tokens.advance();
         }
-        return translated;
+        translated
+    }
+
+    /// Parses a string with interpolated expressions, starting at its first `Interpolation` token.
+    fn interpolation(&mut self) -> Result<expr::Expr, ParseError> {
+        let mut parts = vec![];
+        loop {
+            match self.tokens.peek().token_type {
+                tokens::TokenType::Interpolation(text) => {
+                    self.tokens.advance();
+                    if !text.is_empty() {
+                        parts.push(expr::StringPart::Literal(text.to_string()));
+                    }
+                    parts.push(expr::StringPart::Expr(self.expression()?));
+                }
+                tokens::TokenType::String(text) => {
+                    self.tokens.advance();
+                    if !text.is_empty() {
+                        parts.push(expr::StringPart::Literal(text.to_string()));
+                    }
+                    return Ok(expr::Expr::StringInterp(parts));
+                }
+                _ => return Err(self.error("Expect '}' after interpolated expression.")),
+            }
+        }
+    }
+
+    /// Parses the elements of a tuple after the first one and its comma, up to the right
+    /// parenthesis. There can be a comma after the last element.
+    fn tuple(&mut self, first: expr::Expr) -> Result<expr::Expr, ParseError> {
+        let mut elements = vec![first];
+        while self.tokens.peek().token_type != tokens::TokenType::RightParen {
+            elements.push(self.expression()?);
+            if !self.tokens.match_token(tokens::TokenType::Comma) {
+                break;
+            }
+        }
+        self.tokens.consume(
+            tokens::TokenType::RightParen,
+            "Expect ')' after tuple elements.",
+        )?;
+        Ok(expr::Expr::Tuple(elements))
     }
 
     fn call(&mut self) -> Result<expr::Expr, ParseError> {
         let mut expression = self.primary()?;
         loop {
-            if self.match_token(tokens::TokenType::LeftParen) {
+            if self.tokens.match_token(tokens::TokenType::LeftParen) {
                 expression = self.complete_call(expression)?;
+            } else if self.tokens.match_token(tokens::TokenType::LeftBracket) {
+                let index = self.expression()?;
+                self.tokens
+                    .consume(tokens::TokenType::RightBracket, "Expect ']' after index.")?;
+                expression = expr::Expr::Index(Box::new(expression), Box::new(index));
             } else {
                 break;
             }
@@ -372,42 +1099,50 @@ impl Parser<'_> {
         Ok(expression)
     }
 
-    /// Given a callee, parses the comma-separated arguments.
+    /// Given a callee, parses the comma-separated arguments, optionally followed by a comma.
     ///
     /// The left parenthesis has already been parsed at this point, and this function will consume
     /// the right parenthesis.
     fn complete_call(&mut self, callee: expr::Expr) -> Result<expr::Expr, ParseError> {
         let mut arguments: Vec<expr::Expr> = vec![];
-        if self.current().token_type != tokens::TokenType::RightParen {
+        if self.tokens.peek().token_type != tokens::TokenType::RightParen {
             loop {
                 if arguments.len() >= 255 {
-                    return Err(self.error("Can't have more than 255 arguments."))
+                    return Err(self.error("Can't have more than 255 arguments."));
                 }
                 arguments.push(self.expression()?);
-                if !self.match_token(tokens::TokenType::Comma) {
-                    break
+                if !self.tokens.match_token(tokens::TokenType::Comma) {
+                    break;
+                }
+                // allow a trailing comma after the last argument
+                if self.tokens.peek().token_type == tokens::TokenType::RightParen {
+                    break;
                 }
             }
         }
-        self.consume(tokens::TokenType::RightParen, "Expect ')' after arguments")?;
-        Ok(expr::Expr::Call { callee: Box::new(callee), arguments })
+        self.tokens
+            .consume(tokens::TokenType::RightParen, "Expect ')' after arguments")?;
+        Ok(expr::Expr::Call {
+            callee: Box::new(callee),
+            arguments,
+        })
     }
 }
 
-fn translate_comparison(token: &tokens::TokenType) -> Option<expr::BinaryOperator> {
-    match token {
-        tokens::TokenType::Greater => Some(expr::BinaryOperator::GreaterThan),
-        tokens::TokenType::GreaterEqual => Some(expr::BinaryOperator::GreaterEqualThan),
-        tokens::TokenType::Less => Some(expr::BinaryOperator::LessThan),
-        tokens::TokenType::LessEqual => Some(expr::BinaryOperator::LessEqualThan),
-        _ => None,
+fn make_loop(
+    label: Option<Rc<str>>,
+    condition: expr::Expr,
+    increment: Option<expr::Expr>,
+    body: stmt::Stmt,
+) -> stmt::Stmt {
+    if label.is_none() && increment.is_none() {
+        return stmt::Stmt::While(condition, Box::new(body));
     }
-}
-fn translate_equality(token: &tokens::TokenType) -> Option<expr::BinaryOperator> {
-    match token {
-        tokens::TokenType::BangEqual => Some(expr::BinaryOperator::BangEqual),
-        tokens::TokenType::EqualEqual => Some(expr::BinaryOperator::EqualEqual),
-        _ => None,
+    stmt::Stmt::For {
+        label,
+        condition,
+        increment,
+        body: Box::new(body),
     }
 }
 
@@ -422,26 +1157,251 @@ fn translate_literal(token: &tokens::TokenType) -> Option<object::LoxObject> {
     }
 }
 
-fn translate_unary(token: &tokens::TokenType) -> Option<expr::UnaryOperator> {
-    match token {
-        tokens::TokenType::Bang => Some(expr::UnaryOperator::Bang),
-        tokens::TokenType::Minus => Some(expr::UnaryOperator::Neg),
-        _ => None,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scanning::Scanner;
+
+    fn token_stream(source: &str) -> TokenStream<'_> {
+        TokenStream::new(Scanner::new(source).scan_tokens().0.into_iter())
     }
-}
 
-fn translate_term(token: &tokens::TokenType) -> Option<expr::BinaryOperator> {
-    match token {
-        tokens::TokenType::Minus => Some(expr::BinaryOperator::Sub),
-        tokens::TokenType::Plus => Some(expr::BinaryOperator::Add),
-        _ => None,
+    #[test]
+    fn token_streams_look_two_tokens_ahead() {
+        let mut stream = token_stream("a: b");
+        assert_eq!(stream.peek().lexeme, "a");
+        assert_eq!(stream.peek2().map(|token| token.lexeme), Some(":"));
+        assert_eq!(stream.advance().lexeme, "a");
+        assert_eq!(stream.previous().lexeme, "a");
+        assert_eq!(stream.peek().lexeme, ":");
     }
-}
 
-fn translate_factor(token: &tokens::TokenType) -> Option<expr::BinaryOperator> {
-    match token {
-        tokens::TokenType::Star => Some(expr::BinaryOperator::Mul),
-        tokens::TokenType::Slash => Some(expr::BinaryOperator::Div),
-        _ => None,
+    #[test]
+    fn token_streams_stop_at_eof() {
+        let mut stream = token_stream("1");
+        stream.advance();
+        assert!(stream.is_at_end());
+        assert_eq!(stream.advance().lexeme, "1");
+        assert!(stream.is_at_end());
+        assert_eq!(stream.peek2(), None);
+    }
+
+    #[test]
+    fn token_streams_match_and_consume() {
+        let mut stream = token_stream("( )");
+        assert!(!stream.match_token(tokens::TokenType::RightParen));
+        assert!(stream.match_token(tokens::TokenType::LeftParen));
+        let error = stream
+            .consume(tokens::TokenType::Semicolon, "Expect ';'.")
+            .unwrap_err();
+        assert_eq!((error.at.as_str(), error.column), ("')'", 3));
+        assert!(stream
+            .consume(tokens::TokenType::RightParen, "Expect ')'.")
+            .is_ok());
+    }
+
+    fn parse_errors(source: &str) -> Vec<String> {
+        let (_, errors, _) = Parser::from_source(source).parse();
+        errors
+            .into_iter()
+            .map(|error| error.message().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn scanner_errors_are_returned_in_order() {
+        let (statements, errors, _) = Parser::from_source("var a = 1 @ 2;\nprint(a +);").parse();
+        assert_eq!(statements.len(), 1);
+        assert_eq!(
+            errors,
+            [
+                SyntaxError::Scan(scanning::ScanError {
+                    line: 1,
+                    column: 11,
+                    message: "Unexpected character.".to_string(),
+                }),
+                SyntaxError::Parse(ParseError {
+                    kind: ParseErrorKind::Invalid,
+                    line: 2,
+                    column: 10,
+                    at: "')'".to_string(),
+                    message: "Expected expression.".to_string(),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn unused_local_variables_are_warned_about() {
+        let source = "var global;\n{\n  var used = 1;\n  var unused = used;\n}";
+        let (_, errors, warnings) = Parser::from_source(source).parse();
+        assert!(errors.is_empty());
+        assert_eq!(
+            warnings,
+            [Warning {
+                line: 4,
+                at: "'unused'".to_string(),
+                message: "Local variable 'unused' is never used.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn code_after_break_and_continue_is_warned_about() {
+        let source = "loop {\n  print(1);\n  break;\n  print(2);\n  print(3);\n}\nloop { if (true) continue; break; }";
+        let (_, errors, warnings) = Parser::from_source(source).parse();
+        assert!(errors.is_empty());
+        assert_eq!(
+            warnings,
+            [Warning {
+                line: 4,
+                at: "'print'".to_string(),
+                message: "Unreachable code.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn for_loop_increments_cannot_declare_variables() {
+        assert_eq!(
+            parse_errors("for (var i = 0; i < 10; var j = i) {}"),
+            ["Variable declaration not allowed in for loop increment."]
+        );
+    }
+
+    #[test]
+    fn loop_bodies_are_blocks() {
+        assert_eq!(parse_errors("loop print(1);"), ["Expect '{' after 'loop'."]);
+        assert!(parse_errors("loop { break; }").is_empty());
+    }
+
+    #[test]
+    fn for_loop_increments_can_be_any_expression() {
+        assert!(parse_errors("for (var i = 0; i < 10; i = i + 1) {}").is_empty());
+        assert!(parse_errors("var i; for (i = 0; i < 10; print(i)) i = i + 1;").is_empty());
+        assert!(parse_errors("for (;;) break;").is_empty());
+    }
+
+    #[test]
+    fn the_removed_assert_function_cannot_be_called() {
+        assert_eq!(
+            parse_errors("assert (nil, \"should not be nil\");"),
+            ["Assert takes `condition, message` without parentheses."]
+        );
+        assert!(parse_errors("assert nil, \"should not be nil\";").is_empty());
+        assert!(parse_errors("assert (1, 2) == (1, 2);").is_empty());
+    }
+
+    fn parse_expression(source: &str) -> Result<String, Vec<String>> {
+        Parser::parse_expression(source)
+            .map(|expression| expression.to_string())
+            .map_err(|errors| {
+                errors
+                    .into_iter()
+                    .map(|error| error.message().to_string())
+                    .collect()
+            })
+    }
+
+    #[test]
+    fn single_expressions_can_be_parsed() {
+        assert_eq!(
+            parse_expression("1 + 2 * x"),
+            Ok("(+ 1 (* 2 $x))".to_string())
+        );
+        assert_eq!(
+            parse_expression("max(1, y)"),
+            Ok("(call $max 1 $y)".to_string())
+        );
+    }
+
+    #[test]
+    fn bitwise_operators_have_their_own_precedence_levels() {
+        assert_eq!(
+            parse_expression("1 | 2 ^ 3 & 4 << 5 + 6 == 7"),
+            Ok("(== (| 1 (^ 2 (& 3 (<< 4 (+ 5 6))))) 7)".to_string())
+        );
+    }
+
+    #[test]
+    fn globals_of_aliased_modules_are_variables() {
+        assert_eq!(parse_expression("m.x + 1"), Ok("(+ $m.x 1)".to_string()));
+        assert_eq!(
+            parse_expression("m.1"),
+            Err(vec!["Expect name after '.'.".to_string()])
+        );
+    }
+
+    #[test]
+    fn parsed_expressions_must_span_the_whole_source() {
+        assert_eq!(
+            parse_expression("1 + 2 3"),
+            Err(vec!["Expect end of expression.".to_string()])
+        );
+        assert_eq!(
+            parse_expression("var x = 1;"),
+            Err(vec!["Expected expression.".to_string()])
+        );
+        assert_eq!(
+            parse_expression("1 @ 2"),
+            Err(vec!["Unexpected character.".to_string()])
+        );
+    }
+
+    fn parse_repl_line(source: &str) -> ReplLine {
+        Parser::parse_repl_line(source, Scanner::new(source).scan_tokens().0)
+    }
+
+    #[test]
+    fn repl_lines_can_be_expressions() {
+        match parse_repl_line("1 + x") {
+            ReplLine::Expression(expression) => assert_eq!(expression.to_string(), "(+ 1 $x)"),
+            _ => panic!("expected an expression"),
+        }
+    }
+
+    #[test]
+    fn repl_lines_can_be_statements() {
+        match parse_repl_line("var x = 1; print(x);") {
+            ReplLine::Statements(statements, _) => assert_eq!(statements.len(), 2),
+            _ => panic!("expected statements"),
+        }
+    }
+
+    #[test]
+    fn incomplete_repl_lines_are_errors() {
+        for source in ["if (x) {", "var x = 1", "var x = 1; x + 1"] {
+            match parse_repl_line(source) {
+                ReplLine::Error(errors) => assert_eq!(errors.len(), 1),
+                _ => panic!("expected an error for {}", source),
+            }
+        }
+    }
+
+    #[test]
+    fn only_expressions_ending_the_source_are_unterminated() {
+        let (_, errors, _) = Parser::from_source("1 + 2").parse();
+        assert!(matches!(
+            errors.as_slice(),
+            [SyntaxError::Parse(ParseError {
+                kind: ParseErrorKind::UnterminatedExpression,
+                ..
+            })]
+        ));
+        let (_, errors, _) = Parser::from_source("1 2;").parse();
+        assert!(matches!(
+            errors.as_slice(),
+            [SyntaxError::Parse(ParseError {
+                kind: ParseErrorKind::Invalid,
+                ..
+            })]
+        ));
+        assert!(matches!(parse_repl_line("1 2"), ReplLine::Error(_)));
+    }
+
+    #[test]
+    fn blank_repl_lines_are_empty() {
+        assert!(matches!(parse_repl_line(""), ReplLine::Empty));
+        assert!(matches!(parse_repl_line("  // comment"), ReplLine::Empty));
     }
 }
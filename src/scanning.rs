@@ -1,34 +1,72 @@
+use std::iter;
 use std::str::CharIndices;
 
-use crate::lox;
 use crate::tokens;
 use crate::tokens::TokenType;
 
+/// An error found while scanning, like an unexpected character.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScanError {
+    pub line: usize,
+    /// Column (counted in characters, starting at 1) at which the error is.
+    pub column: usize,
+    pub message: String,
+}
+
 pub struct Scanner<'s> {
     source: &'s str,
-    iter: prepeek::Prepeek<CharIndices<'s>, 2>,
-    tokens: Vec<crate::tokens::Token<'s>>,
+    iter: prefetch::Prefetched<CharIndices<'s>, 2>,
+    /// The token (or error) found by the last call to `scan_token`, if any.
+    scanned: Option<Result<tokens::Token<'s>, ScanError>>,
+    /// Whether the `EOF` token has been returned.
+    finished: bool,
     start: usize,
+    /// Column at which the token being scanned starts.
+    start_column: usize,
     line: usize,
+    /// Byte offset at which the current line starts.
+    line_start: usize,
+    /// Braces left open in each `${...}` of a string being scanned, innermost last.
+    interpolations: Vec<usize>,
 }
 
+/// The reserved words of the language, which `TokenType::from_identifier` turns into keywords.
+pub const KEYWORDS: &[&str] = &[
+    "and", "assert", "break", "catch", "class", "const", "continue", "else", "export", "false",
+    "finally", "for", "fun", "if", "import", "in", "loop", "nil", "not", "or", "repeat", "return",
+    "super", "this", "throw", "true", "try", "var", "while",
+];
+
 impl TokenType<'_> {
-    fn from_identifier(identifier: &str) -> TokenType {
+    fn from_identifier(identifier: &str) -> TokenType<'_> {
         match identifier {
             "and" => TokenType::And,
+            "assert" => TokenType::Assert,
+            "break" => TokenType::Break,
+            "catch" => TokenType::Catch,
             "class" => TokenType::Class,
+            "const" => TokenType::Const,
+            "continue" => TokenType::Continue,
             "else" => TokenType::Else,
+            "export" => TokenType::Export,
             "false" => TokenType::False,
+            "finally" => TokenType::Finally,
             "for" => TokenType::For,
             "fun" => TokenType::Fun,
             "if" => TokenType::If,
+            "import" => TokenType::Import,
+            "in" => TokenType::In,
+            "loop" => TokenType::Loop,
             "nil" => TokenType::Nil,
+            "not" => TokenType::Not,
             "or" => TokenType::Or,
-            "print" => TokenType::Print,
+            "repeat" => TokenType::Repeat,
             "return" => TokenType::Return,
             "super" => TokenType::Super,
             "this" => TokenType::This,
+            "throw" => TokenType::Throw,
             "true" => TokenType::True,
+            "try" => TokenType::Try,
             "var" => TokenType::Var,
             "while" => TokenType::While,
             s => TokenType::Identifier(s),
@@ -40,31 +78,73 @@ impl<'s> Scanner<'s> {
     pub fn new(source: &'s str) -> Scanner<'s> {
         Scanner {
             source,
-            iter: prepeek::Prepeek::new(source.char_indices()),
-            tokens: vec![],
+            iter: prefetch::Prefetched::new(source.char_indices()),
+            scanned: None,
+            finished: false,
             start: 0,
+            start_column: 1,
             line: 1,
+            line_start: 0,
+            interpolations: vec![],
+        }
+    }
+
+    /// Scans the whole source, returning its tokens along with the errors found on the way.
+    ///
+    /// Scanning goes on after an error, from the end of the statement or line it is in.
+    pub fn scan_tokens(self) -> (Vec<tokens::Token<'s>>, Vec<ScanError>) {
+        let mut tokens = vec![];
+        let mut errors = vec![];
+        for scanned in self.into_token_iter() {
+            match scanned {
+                Ok(token) => tokens.push(token),
+                Err(error) => errors.push(error),
+            }
         }
+        (tokens, errors)
     }
 
-    pub fn scan_tokens(mut self) -> Vec<tokens::Token<'s>> {
+    /// Scans the source lazily, one token (or error) at a time, ending with the `EOF` token.
+    pub fn into_token_iter(mut self) -> impl Iterator<Item = Result<tokens::Token<'s>, ScanError>> {
+        iter::from_fn(move || self.next_token())
+    }
+
+    fn next_token(&mut self) -> Option<Result<tokens::Token<'s>, ScanError>> {
         while let Some((start, _)) = self.iter.peek() {
             self.start = *start;
+            self.start_column = self.column(self.start);
             self.scan_token();
+            if let Some(scanned) = self.scanned.take() {
+                return Some(scanned);
+            }
         }
-
-        self.tokens
-            .push(tokens::Token::new(tokens::TokenType::EOF, "", self.line));
-
-        self.tokens
+        if self.finished {
+            return None;
+        }
+        // the source ended inside of an interpolation
+        if !self.interpolations.is_empty() {
+            self.interpolations.clear();
+            self.error(self.source.len(), "Unterminated string.");
+            return self.scanned.take();
+        }
+        self.finished = true;
+        Some(Ok(tokens::Token::new(
+            tokens::TokenType::EOF,
+            "",
+            self.line,
+            self.column(self.source.len()),
+            self.source.len(),
+        )))
     }
 
     fn add_token(&mut self, token_type: tokens::TokenType<'s>) {
-        self.tokens.push(tokens::Token {
+        self.scanned = Some(Ok(tokens::Token {
             token_type,
             lexeme: self.current_text(),
             line: self.line,
-        })
+            column: self.start_column,
+            offset: self.start,
+        }))
     }
 
     fn scan_token(&mut self) {
@@ -74,14 +154,37 @@ impl<'s> Scanner<'s> {
         match startchar {
             '(' => self.add_token(TokenType::LeftParen),
             ')' => self.add_token(TokenType::RightParen),
-            '{' => self.add_token(TokenType::LeftBrace),
-            '}' => self.add_token(TokenType::RightBrace),
+            '{' => {
+                if let Some(open) = self.interpolations.last_mut() {
+                    *open += 1;
+                }
+                self.add_token(TokenType::LeftBrace)
+            }
+            '}' => match self.interpolations.last_mut() {
+                // the end of an interpolation, the string goes on
+                Some(0) => {
+                    self.interpolations.pop();
+                    self.string()
+                }
+                Some(open) => {
+                    *open -= 1;
+                    self.add_token(TokenType::RightBrace)
+                }
+                None => self.add_token(TokenType::RightBrace),
+            },
+            '[' => self.add_token(TokenType::LeftBracket),
+            ']' => self.add_token(TokenType::RightBracket),
             ',' => self.add_token(TokenType::Comma),
+            ':' => self.add_token(TokenType::Colon),
             '.' => self.add_token(TokenType::Dot),
             '-' => self.add_token(TokenType::Minus),
             '+' => self.add_token(TokenType::Plus),
             ';' => self.add_token(TokenType::Semicolon),
             '*' => self.add_token(TokenType::Star),
+            '&' => self.add_token(TokenType::Ampersand),
+            '|' => self.add_token(TokenType::Pipe),
+            '^' => self.add_token(TokenType::Caret),
+            '~' => self.add_token(TokenType::Tilde),
             '!' => {
                 let token = if self.current_matches('=') {
                     TokenType::BangEqual
@@ -101,6 +204,8 @@ impl<'s> Scanner<'s> {
             '<' => {
                 let token = if self.current_matches('=') {
                     TokenType::LessEqual
+                } else if self.current_matches('<') {
+                    TokenType::LessLess
                 } else {
                     TokenType::Less
                 };
@@ -109,6 +214,8 @@ impl<'s> Scanner<'s> {
             '>' => {
                 let token = if self.current_matches('=') {
                     TokenType::GreaterEqual
+                } else if self.current_matches('>') {
+                    TokenType::GreaterGreater
                 } else {
                     TokenType::Greater
                 };
@@ -124,20 +231,37 @@ impl<'s> Scanner<'s> {
             ' ' | '\r' | '\t' => {}
             '\n' => {
                 self.line += 1;
+                self.line_start = self.start + 1;
             }
             '"' => self.string(),
             c => {
-                if c.is_digit(10) {
+                if c.is_ascii_digit() {
                     self.number();
-                } else if c.is_alphabetic() {
+                } else if c.is_alphabetic() || c == '_' {
                     self.identifier();
                 } else {
-                    lox::error(self.line, "Unexpected character.")
+                    self.error(self.start, "Unexpected character.");
+                    // skip the rest of the statement (or line), which would only add to the errors,
+                    // but keep blocks and strings balanced
+                    self.advance_while(|c| !matches!(c, ';' | '\n' | '{' | '}' | '"'))
                 }
             }
         }
     }
 
+    fn error(&mut self, position: usize, message: &str) {
+        self.scanned = Some(Err(ScanError {
+            line: self.line,
+            column: self.column(position),
+            message: message.to_string(),
+        }))
+    }
+
+    /// Column (counted in characters, starting at 1) of a byte offset on the current line.
+    fn column(&self, position: usize) -> usize {
+        self.source[self.line_start..position].chars().count() + 1
+    }
+
     fn current_text(&self) -> &'s str {
         match self.iter.peek() {
             Some((current, _)) => &self.source[self.start..*current],
@@ -146,22 +270,22 @@ impl<'s> Scanner<'s> {
     }
 
     fn identifier(&mut self) {
-        self.advance_while(|c| c.is_alphanumeric());
+        self.advance_while(|c| c.is_alphanumeric() || c == '_');
         let identifier = self.current_text();
         self.add_token(TokenType::from_identifier(identifier));
     }
 
     fn number(&mut self) {
         // consume consecutive digits
-        self.advance_while(|c| c.is_digit(10));
+        self.advance_while(|c| c.is_ascii_digit());
         // consume decimal part
         let c1 = self.iter.peek().cloned();
         let c2 = self.iter.peek_nth(1).cloned();
         match (c1, c2) {
-            (Some((_, '.')), Some((_, c))) if c.is_digit(10) => {
+            (Some((_, '.')), Some((_, c))) if c.is_ascii_digit() => {
                 // consume '.' and following digits
                 self.advance();
-                self.advance_while(|c| c.is_digit(10));
+                self.advance_while(|c| c.is_ascii_digit());
             }
             _ => {}
         }
@@ -169,25 +293,37 @@ impl<'s> Scanner<'s> {
     }
 
     fn advance_while(&mut self, predicate: impl Fn(char) -> bool) {
-        while let Some((_, c)) = self.iter.peek() {
+        while let Some((position, c)) = self.iter.peek() {
             if !predicate(*c) {
                 break;
             }
             if *c == '\n' {
-                self.line += 1
+                self.line += 1;
+                self.line_start = position + 1;
             }
             self.iter.next();
         }
     }
 
+    /// Scans the rest of a string, which starts after a `"` or after the `}` of an interpolation.
+    ///
+    /// A string containing `${` ends there with an `Interpolation` token, and the tokens of the
+    /// interpolated expression follow. The string resumes at the matching `}`.
     fn string(&mut self) {
-        self.advance_while(|c| c != '"');
-        match self.iter.next() {
-            Some((current, '"')) => {
-                let value = &self.source[self.start + 1..current];
-                self.add_token(TokenType::String(value));
+        loop {
+            match self.iter.next() {
+                Some((current, '"')) => {
+                    let value = &self.source[self.start + 1..current];
+                    return self.add_token(TokenType::String(value));
+                }
+                Some((current, '$')) if self.current_matches('{') => {
+                    let value = &self.source[self.start + 1..current];
+                    self.interpolations.push(0);
+                    return self.add_token(TokenType::Interpolation(value));
+                }
+                Some(_) => {}
+                None => return self.error(self.source.len(), "Unterminated string."),
             }
-            _ => lox::error(self.line, "Unterminated string."),
         }
     }
 
@@ -212,15 +348,31 @@ mod tests {
 
     use super::*;
 
+    #[test]
+    fn keywords_are_scanned_as_keywords() {
+        for keyword in KEYWORDS {
+            let token_type = TokenType::from_identifier(keyword);
+            assert!(
+                !matches!(token_type, TokenType::Identifier(_)),
+                "{}",
+                keyword
+            );
+        }
+        assert!(matches!(
+            TokenType::from_identifier("foo"),
+            TokenType::Identifier("foo")
+        ));
+    }
+
     #[test]
     fn can_parse_braces() {
         let scanner = Scanner::new("{}");
         let expected = vec![
-            Token::new(TokenType::LeftBrace, "{", 1),
-            Token::new(TokenType::RightBrace, "}", 1),
-            Token::new(TokenType::EOF, "", 1),
+            Token::new(TokenType::LeftBrace, "{", 1, 1, 0),
+            Token::new(TokenType::RightBrace, "}", 1, 2, 1),
+            Token::new(TokenType::EOF, "", 1, 3, 2),
         ];
-        assert_eq!(scanner.scan_tokens(), expected)
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
     }
 
     #[test]
@@ -231,30 +383,234 @@ mod tests {
                 TokenType::String("this is a string"),
                 "\"this is a string\"",
                 1,
+                1,
+                0,
             ),
-            Token::new(TokenType::EOF, "", 1),
+            Token::new(TokenType::EOF, "", 1, 19, 18),
+        ];
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
+    }
+
+    #[test]
+    fn can_parse_multi_byte_strings() {
+        for source in ["\"héllo\"", "\"日本語\"", "\"\u{d7ff}\u{e000}\u{10000}𝄞\""] {
+            let (tokens, _) = Scanner::new(source).scan_tokens();
+            let value = &source[1..source.len() - 1];
+            assert_eq!(
+                tokens[0],
+                Token::new(TokenType::String(value), source, 1, 1, 0)
+            );
+            // the lexeme spans every byte of the encoding, quotes included
+            assert_eq!(tokens[0].lexeme.len(), value.len() + 2);
+        }
+    }
+
+    #[test]
+    fn strings_can_contain_null_characters() {
+        let scanner = Scanner::new("\"a\0b\"");
+        let expected = vec![
+            Token::new(TokenType::String("a\0b"), "\"a\0b\"", 1, 1, 0),
+            Token::new(TokenType::EOF, "", 1, 6, 5),
         ];
-        assert_eq!(scanner.scan_tokens(), expected)
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
+    }
+
+    #[test]
+    fn interpolated_strings_are_split_around_their_expressions() {
+        let (tokens, errors) = Scanner::new("\"a ${b + \"${c}\"} d\"").scan_tokens();
+        let types: Vec<_> = tokens.into_iter().map(|token| token.token_type).collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::Interpolation("a "),
+                TokenType::Identifier("b"),
+                TokenType::Plus,
+                TokenType::Interpolation(""),
+                TokenType::Identifier("c"),
+                TokenType::String(""),
+                TokenType::String(" d"),
+                TokenType::EOF,
+            ]
+        );
+        assert_eq!(errors, vec![]);
     }
 
     #[test]
     fn can_parse_number() {
         let scanner = Scanner::new("123.456");
         let expected = vec![
-            Token::new(TokenType::Number(123.456), "123.456", 1),
-            Token::new(TokenType::EOF, "", 1),
+            Token::new(TokenType::Number(123.456), "123.456", 1, 1, 0),
+            Token::new(TokenType::EOF, "", 1, 8, 7),
+        ];
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
+    }
+
+    #[test]
+    fn identifiers_can_contain_underscores() {
+        let scanner = Scanner::new("_assert_eq1");
+        let expected = vec![
+            Token::new(TokenType::Identifier("_assert_eq1"), "_assert_eq1", 1, 1, 0),
+            Token::new(TokenType::EOF, "", 1, 12, 11),
+        ];
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
+    }
+
+    #[test]
+    fn identifiers_can_be_unicode() {
+        for source in ["café", "名前", "α_value", "_underscore", "_"] {
+            let expected = vec![
+                Token::new(TokenType::Identifier(source), source, 1, 1, 0),
+                Token::new(
+                    TokenType::EOF,
+                    "",
+                    1,
+                    source.chars().count() + 1,
+                    source.len(),
+                ),
+            ];
+            assert_eq!(Scanner::new(source).scan_tokens(), (expected, vec![]))
+        }
+    }
+
+    #[test]
+    fn identifiers_cannot_start_with_a_digit() {
+        let scanner = Scanner::new("1var");
+        let expected = vec![
+            Token::new(TokenType::Number(1.0), "1", 1, 1, 0),
+            Token::new(TokenType::Var, "var", 1, 2, 1),
+            Token::new(TokenType::EOF, "", 1, 5, 4),
         ];
-        assert_eq!(scanner.scan_tokens(), expected)
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
     }
 
     #[test]
     fn lines_are_tracked() {
         let scanner = Scanner::new("\n\n()");
         let expected = vec![
-            Token::new(TokenType::LeftParen, "(", 3),
-            Token::new(TokenType::RightParen, ")", 3),
-            Token::new(TokenType::EOF, "", 3),
+            Token::new(TokenType::LeftParen, "(", 3, 1, 2),
+            Token::new(TokenType::RightParen, ")", 3, 2, 3),
+            Token::new(TokenType::EOF, "", 3, 3, 4),
         ];
-        assert_eq!(scanner.scan_tokens(), expected)
+        assert_eq!(scanner.scan_tokens(), (expected, vec![]))
+    }
+
+    #[test]
+    fn columns_are_tracked() {
+        let (tokens, _) = Scanner::new("var été\n  = \"a\";").scan_tokens();
+        let columns: Vec<_> = tokens
+            .iter()
+            .map(|token| (token.line, token.column))
+            .collect();
+        assert_eq!(columns, [(1, 1), (1, 5), (2, 3), (2, 5), (2, 8), (2, 9)]);
+    }
+
+    #[test]
+    fn offsets_are_in_bytes() {
+        let (tokens, _) = Scanner::new("var été\n  = \"a\";").scan_tokens();
+        let offsets: Vec<_> = tokens.iter().map(|token| token.offset).collect();
+        assert_eq!(offsets, [0, 4, 12, 14, 17, 18]);
+    }
+
+    #[test]
+    fn errors_are_returned_with_the_tokens() {
+        let (tokens, errors) = Scanner::new(
+            "1 @ x
+  # 2; 3",
+        )
+        .scan_tokens();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::new(TokenType::Number(1.0), "1", 1, 1, 0),
+                Token::new(TokenType::Semicolon, ";", 2, 6, 11),
+                Token::new(TokenType::Number(3.0), "3", 2, 8, 13),
+                Token::new(TokenType::EOF, "", 2, 9, 14),
+            ]
+        );
+        assert_eq!(
+            errors,
+            vec![
+                ScanError {
+                    line: 1,
+                    column: 3,
+                    message: "Unexpected character.".to_string(),
+                },
+                ScanError {
+                    line: 2,
+                    column: 3,
+                    message: "Unexpected character.".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn scanning_resumes_at_the_next_statement() {
+        let source = "var a = 1 @ 2;\nprint(a # 1); { var b = $$ 3; }\n\"${x ` y}\"";
+        let (tokens, errors) = Scanner::new(source).scan_tokens();
+        let positions: Vec<_> = errors
+            .iter()
+            .map(|error| (error.line, error.column))
+            .collect();
+        assert_eq!(positions, [(1, 11), (2, 9), (2, 25), (3, 6)]);
+        let lexemes: Vec<_> = tokens.iter().map(|token| token.lexeme).collect();
+        assert_eq!(
+            lexemes,
+            [
+                "var", "a", "=", "1", ";", "print", "(", "a", ";", "{", "var", "b", "=", ";", "}",
+                "\"${", "x", "}\"", ""
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_strings_are_errors() {
+        let (tokens, errors) = Scanner::new("\"abc").scan_tokens();
+        assert_eq!(tokens, vec![Token::new(TokenType::EOF, "", 1, 5, 4)]);
+        assert_eq!(
+            errors,
+            vec![ScanError {
+                line: 1,
+                column: 5,
+                message: "Unterminated string.".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn lazy_scanning_matches_eager_scanning() {
+        let source = "var answer = 42.5; // the answer\n\
+            while (answer >= 1 and !done) {\n\
+              print(\"left: \" + answer);\n\
+              answer = answer / 2 @ 1;\n\
+            }\n\
+            \"unterminated";
+        let (tokens, errors) = Scanner::new(source).scan_tokens();
+        let lazy: Vec<_> = Scanner::new(source).into_token_iter().collect();
+        assert_eq!(
+            lazy.iter()
+                .filter_map(|scanned| scanned.clone().ok())
+                .collect::<Vec<_>>(),
+            tokens
+        );
+        assert_eq!(
+            lazy.iter()
+                .filter_map(|scanned| scanned.clone().err())
+                .collect::<Vec<_>>(),
+            errors
+        );
+        assert_eq!(errors.len(), 2);
+        assert_eq!(tokens.last().unwrap().token_type, TokenType::EOF);
+    }
+
+    #[test]
+    fn lazy_scanning_stops_after_eof() {
+        let mut tokens = Scanner::new("1").into_token_iter();
+        assert!(tokens.next().is_some());
+        assert_eq!(
+            tokens.next(),
+            Some(Ok(Token::new(TokenType::EOF, "", 1, 2, 1)))
+        );
+        assert_eq!(tokens.next(), None);
     }
 }
@@ -1,17 +1,120 @@
+use std::rc::Rc;
+
 use crate::expr;
 
+#[derive(Clone)]
 pub enum Stmt {
     Expression(expr::Expr),
-    Print(expr::Expr),
     Block(Vec<Stmt>),
     Var {
-        name: String,
+        name: Rc<str>,
         initializer: Option<expr::Expr>,
     },
+    /// A variable that can't be assigned after its declaration.
+    Const {
+        name: Rc<str>,
+        initializer: expr::Expr,
+    },
     If {
         condition: expr::Expr,
         then_branch: Box<Stmt>,
         else_branch: Option<Box<Stmt>>,
     },
     While(expr::Expr, Box<Stmt>),
+    /// A loop with a label or an increment, which runs after the body even when it continues.
+    For {
+        label: Option<Rc<str>>,
+        condition: expr::Expr,
+        increment: Option<expr::Expr>,
+        body: Box<Stmt>,
+    },
+    /// Runs the body until a `break` leaves it.
+    Loop {
+        body: Box<Stmt>,
+    },
+    /// Runs the body a number of times.
+    Repeat {
+        count: expr::Expr,
+        body: Box<Stmt>,
+    },
+    Break {
+        label: Option<Rc<str>>,
+    },
+    Continue {
+        label: Option<Rc<str>>,
+    },
+    TryCatch {
+        body: Box<Stmt>,
+        catch_var: Rc<str>,
+        catch_body: Box<Stmt>,
+        finally_body: Option<Box<Stmt>>,
+    },
+    Throw(expr::Expr),
+    /// Fails with the message, or with the condition as written, if the condition is falsey.
+    Assert {
+        condition: expr::Expr,
+        message: Option<expr::Expr>,
+        source: String,
+    },
+    /// Runs another file and defines the globals it exports in the current scope, named like
+    /// `alias.name` if there is an alias.
+    Import {
+        path: String,
+        alias: Option<Rc<str>>,
+    },
+    /// A global declaration that modules importing this file can see.
+    Export(Box<Stmt>),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn variable(name: &str) -> expr::Expr {
+        expr::Expr::Variable(name.into())
+    }
+
+    /// The statement in the then branch of the `if` nested in two blocks.
+    fn then_branch(stmt: &mut Stmt) -> &mut Stmt {
+        let Stmt::Block(outer) = stmt else {
+            panic!("expected a block");
+        };
+        let Stmt::Block(inner) = &mut outer[0] else {
+            panic!("expected a block");
+        };
+        let Stmt::If { then_branch, .. } = &mut inner[0] else {
+            panic!("expected an if statement");
+        };
+        then_branch
+    }
+
+    fn printed(stmt: &Stmt) -> String {
+        match stmt {
+            Stmt::Expression(expr) => expr.to_string(),
+            _ => panic!("expected an expression statement"),
+        }
+    }
+
+    #[test]
+    fn clones_are_deep() {
+        let mut original = Stmt::Block(vec![Stmt::Block(vec![Stmt::If {
+            condition: variable("a"),
+            then_branch: Box::new(Stmt::Expression(variable("b"))),
+            else_branch: Some(Box::new(Stmt::Expression(variable("c")))),
+        }])]);
+        let mut clone = original.clone();
+        *then_branch(&mut clone) = Stmt::Expression(variable("d"));
+        assert_eq!(printed(then_branch(&mut original)), "$b");
+        assert_eq!(printed(then_branch(&mut clone)), "$d");
+        let Stmt::Block(outer) = &clone else {
+            panic!("expected a block");
+        };
+        let Stmt::Block(inner) = &outer[0] else {
+            panic!("expected a block");
+        };
+        let Stmt::If { else_branch, .. } = &inner[0] else {
+            panic!("expected an if statement");
+        };
+        assert_eq!(printed(else_branch.as_ref().unwrap()), "$c");
+    }
 }
@@ -1,17 +1,27 @@
+use std::fmt;
+
 #[derive(Debug, Clone, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
 pub enum TokenType<'a> {
     // Single-character tokens.
     LeftParen,
     RightParen,
     LeftBrace,
     RightBrace,
+    LeftBracket,
+    RightBracket,
     Comma,
+    Colon,
     Dot,
     Minus,
     Plus,
     Semicolon,
     Slash,
     Star,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
 
     // One or two character tokens.
     Bang,
@@ -20,48 +30,167 @@ pub enum TokenType<'a> {
     EqualEqual,
     Greater,
     GreaterEqual,
+    GreaterGreater,
     Less,
     LessEqual,
+    LessLess,
 
     // Literals.
     Identifier(&'a str),
     String(&'a str),
+    /// The part of a string before an interpolated `${...}` expression.
+    Interpolation(&'a str),
     Number(f32),
 
     // Keywords.
     And,
+    Assert,
+    Break,
+    Catch,
     Class,
+    Const,
+    Continue,
     Else,
+    Export,
     False,
+    Finally,
     Fun,
     For,
     If,
+    Import,
+    In,
+    Loop,
     Nil,
+    Not,
     Or,
-    Print,
+    Repeat,
     Return,
     Super,
     This,
+    Throw,
     True,
+    Try,
     Var,
     While,
 
     EOF,
 }
 
+impl fmt::Display for TokenType<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            TokenType::LeftParen => "(",
+            TokenType::RightParen => ")",
+            TokenType::LeftBracket => "[",
+            TokenType::RightBracket => "]",
+            TokenType::LeftBrace => "{",
+            TokenType::RightBrace => "}",
+            TokenType::Comma => ",",
+            TokenType::Colon => ":",
+            TokenType::Dot => ".",
+            TokenType::Minus => "-",
+            TokenType::Plus => "+",
+            TokenType::Semicolon => ";",
+            TokenType::Slash => "/",
+            TokenType::Star => "*",
+            TokenType::Ampersand => "&",
+            TokenType::Pipe => "|",
+            TokenType::Caret => "^",
+            TokenType::Tilde => "~",
+            TokenType::Bang => "!",
+            TokenType::BangEqual => "!=",
+            TokenType::Equal => "=",
+            TokenType::EqualEqual => "==",
+            TokenType::Greater => ">",
+            TokenType::GreaterEqual => ">=",
+            TokenType::GreaterGreater => ">>",
+            TokenType::Less => "<",
+            TokenType::LessEqual => "<=",
+            TokenType::LessLess => "<<",
+            TokenType::Identifier(_) => "identifier",
+            TokenType::String(_) | TokenType::Interpolation(_) => "string",
+            TokenType::Number(_) => "number",
+            TokenType::And => "and",
+            TokenType::Assert => "assert",
+            TokenType::Break => "break",
+            TokenType::Catch => "catch",
+            TokenType::Class => "class",
+            TokenType::Const => "const",
+            TokenType::Continue => "continue",
+            TokenType::Else => "else",
+            TokenType::Export => "export",
+            TokenType::False => "false",
+            TokenType::Finally => "finally",
+            TokenType::Fun => "fun",
+            TokenType::For => "for",
+            TokenType::If => "if",
+            TokenType::Import => "import",
+            TokenType::In => "in",
+            TokenType::Loop => "loop",
+            TokenType::Nil => "nil",
+            TokenType::Not => "not",
+            TokenType::Or => "or",
+            TokenType::Repeat => "repeat",
+            TokenType::Return => "return",
+            TokenType::Super => "super",
+            TokenType::This => "this",
+            TokenType::Throw => "throw",
+            TokenType::True => "true",
+            TokenType::Try => "try",
+            TokenType::Var => "var",
+            TokenType::While => "while",
+            TokenType::EOF => "end",
+        };
+        write!(f, "{}", s)
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token<'a> {
     pub token_type: TokenType<'a>,
     pub lexeme: &'a str,
     pub line: usize,
+    /// Column (counted in characters, starting at 1) at which the token starts.
+    pub column: usize,
+    /// Byte offset in the source at which the token starts.
+    pub offset: usize,
 }
 
 impl Token<'_> {
-    pub fn new<'a>(token_type: TokenType<'a>, lexeme: &'a str, line: usize) -> Token<'a> {
+    pub fn new<'a>(
+        token_type: TokenType<'a>,
+        lexeme: &'a str,
+        line: usize,
+        column: usize,
+        offset: usize,
+    ) -> Token<'a> {
         Token {
             token_type,
             lexeme,
             line,
+            column,
+            offset,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn token_types_display_as_in_source() {
+        assert_eq!(TokenType::LeftParen.to_string(), "(");
+        assert_eq!(TokenType::BangEqual.to_string(), "!=");
+        assert_eq!(TokenType::LessLess.to_string(), "<<");
+        assert_eq!(TokenType::While.to_string(), "while");
+    }
+
+    #[test]
+    fn literals_display_their_kind() {
+        assert_eq!(TokenType::Identifier("count").to_string(), "identifier");
+        assert_eq!(TokenType::String("text").to_string(), "string");
+        assert_eq!(TokenType::Number(1.0).to_string(), "number");
+        assert_eq!(TokenType::EOF.to_string(), "end");
+    }
+}
@@ -0,0 +1,477 @@
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+use std::process::Command;
+use std::process::Output;
+use std::process::Stdio;
+
+fn run_with_stdin(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not run the interpreter");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn piped_stdin_runs_as_a_program() {
+    let output = run_with_stdin(&[], "var a = 40;\nprint(a + 2);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn piped_stdin_reports_errors() {
+    let output = run_with_stdin(&[], "print(-\"a\");\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(!String::from_utf8_lossy(&output.stderr).is_empty());
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn stdin_flag_runs_stdin_as_a_program() {
+    let output = run_with_stdin(&["--stdin"], "var a = 40;\nprint(a + 2);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+    assert!(output.status.success());
+}
+
+#[test]
+fn stdin_flag_cannot_be_used_with_a_file() {
+    let output = run_with_stdin(&["--stdin", "program.lox"], "print(1);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("cannot be used with"));
+    assert!(!output.status.success());
+}
+
+#[test]
+fn missing_files_exit_with_an_io_error() {
+    let output = run_with_stdin(&["does_not_exist.lox"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Could not read 'does_not_exist.lox'"));
+    assert_eq!(output.status.code(), Some(74));
+}
+
+#[test]
+fn break_outside_of_a_loop_is_an_error() {
+    let output = run_with_stdin(&[], "print(1);\nbreak;\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Can't use 'break' outside of a loop.")
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn continue_outside_of_a_loop_is_an_error() {
+    let output = run_with_stdin(&[], "if (true) continue;\n");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Can't use 'continue' outside of a loop.")
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn break_to_an_unknown_label_is_an_error() {
+    let output = run_with_stdin(&[], "outer: while (true) { while (true) break inner; }\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Error at 'inner': No enclosing loop with this label."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn every_top_level_syntax_error_is_reported() {
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("syntax_errors.lox");
+    fs::write(&program, "print(1);\nvar = 2;\nprint(3 +);\n").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg(&program)
+        .output()
+        .expect("could not run the interpreter");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let program = program.display();
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    let expected = format!("[{}:2, col 5] Error at '=': Expect variable name.", program);
+    assert!(stderr.contains(&expected));
+    let expected = format!("[{}:3, col 10] Error at ')': Expected expression.", program);
+    assert!(stderr.contains(&expected));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn uncaught_throw_is_a_runtime_error() {
+    let output = run_with_stdin(&[], "throw \"oops\";\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Uncaught exception: oops"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn failing_assertions_report_their_message() {
+    let output = run_with_stdin(&[], "assert_eq(1, 2, \"numbers\");\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Expected 1 but got 2: numbers"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn only_a_single_trailing_comma_is_allowed_in_calls() {
+    for source in ["print(,);\n", "print(1,,);\n"] {
+        let output = run_with_stdin(&[], source);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("Error at ',': Expected expression.")
+        );
+        assert_eq!(output.status.code(), Some(65));
+    }
+}
+
+#[test]
+fn identifiers_cannot_start_with_a_digit() {
+    let output = run_with_stdin(&[], "var 1var = 1;\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Error at '1': Expect variable name."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn identifiers_cannot_contain_emoji() {
+    for source in ["var 😀 = 1;\n", "var a😀 = 1;\n"] {
+        let output = run_with_stdin(&[], source);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("Unexpected character."));
+        assert_eq!(output.status.code(), Some(65));
+    }
+}
+
+#[test]
+fn unexpected_characters_report_their_column() {
+    let output = run_with_stdin(&[], "var a = 1;\nvar b = a @ 2;\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[<stdin>:2, col 11] Error at : Unexpected character."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn every_unexpected_character_is_reported() {
+    let output = run_with_stdin(&[], "var a = 1 @ 2;\nprint(a # 1);\n{ var b = $; }\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for location in [
+        "[<stdin>:1, col 11]",
+        "[<stdin>:2, col 9]",
+        "[<stdin>:3, col 11]",
+    ] {
+        assert!(stderr.contains(&format!("{} Error at : Unexpected character.", location)));
+    }
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn columns_count_characters_rather_than_bytes() {
+    let output = run_with_stdin(&[], "var été = 1 # 2;\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("[<stdin>:1, col 13]"));
+}
+
+#[test]
+fn unterminated_strings_report_the_column_where_input_ends() {
+    let output = run_with_stdin(&[], "print(\"a\");\nprint(\"abc");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[<stdin>:2, col 11] Error at : Unterminated string."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn for_loop_variables_are_not_visible_after_the_loop() {
+    let output = run_with_stdin(&[], "for (var i = 0; i < 3; i = i + 1) {}\nprint(i);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undefined variable 'i'."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn variables_cannot_be_redeclared_in_the_same_local_scope() {
+    let output = run_with_stdin(&[], "{\n  var x = 1;\n  var x = 2;\n}\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "[<stdin>:3, col 7] Error at 'x': Already a variable with the name 'x' in this scope."
+    ));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn unused_local_variables_are_warned_about() {
+    let output = run_with_stdin(&[], "{\n  var unused = 1;\n  print(2);\n}\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "2\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[<stdin>:2] Warning at 'unused': Local variable 'unused' is never used."));
+    assert!(output.status.success());
+}
+
+#[test]
+fn code_after_break_is_warned_about_and_removed() {
+    let output = run_with_stdin(&[], "loop {\n  print(1);\n  break;\n  print(2);\n}\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[<stdin>:4] Warning at 'print': Unreachable code."));
+    assert!(output.status.success());
+}
+
+#[test]
+fn warnings_can_be_disabled() {
+    let output = run_with_stdin(&["--no-warnings"], "{\n  var unused = 1;\n}\n");
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+    assert!(output.status.success());
+}
+
+#[test]
+fn variables_used_in_nested_scopes_are_not_warned_about() {
+    let output = run_with_stdin(
+        &[],
+        "{\n  var a = 1;\n  {\n    print(a);\n  }\n}\nvar global = 1;\n",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stderr), "");
+}
+
+#[test]
+fn profile_prints_the_evaluation_counts() {
+    let output = run_with_stdin(&["--profile"], "var a = 1 + 2;\nprint(a);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "3\n");
+    // `1 + 2` is folded into a single literal before the program runs
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Profiler: 2 statements, 4 expressions evaluated"));
+    assert!(output.status.success());
+}
+
+#[test]
+fn output_can_be_limited_to_a_number_of_lines() {
+    let output = run_with_stdin(
+        &["--max-output-lines", "10"],
+        "for (var i = 0; i < 200; i = i + 1) print(i);\n",
+    );
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(stdout.lines().count(), 10);
+    assert!(stdout.ends_with("9\n"));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[Output truncated after 10 lines]\n"
+    );
+    assert!(output.status.success());
+}
+
+#[test]
+fn programs_running_past_the_timeout_are_stopped() {
+    let output = run_with_stdin(&["--timeout", "0.1"], "print(1);\nwhile (true) {}\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "[Timeout after 0.1 seconds]\n"
+    );
+    assert_eq!(output.status.code(), Some(1));
+}
+
+#[test]
+fn programs_finishing_before_the_timeout_run_normally() {
+    let output = run_with_stdin(&["--timeout", "10"], "print(1);\nprint(-\"a\");\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("Timeout"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn timeouts_must_be_a_number_of_seconds() {
+    let output = run_with_stdin(&["--timeout=-1"], "");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("'-1' is not a number of seconds"));
+    assert_eq!(output.status.code(), Some(2));
+    let output = run_with_stdin(&["--timeout", "1e30"], "print(1);\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("'1e30' seconds is too long a timeout")
+    );
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn circular_imports_are_an_error() {
+    let program = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/spec/modules/circular_a.lox");
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg(program)
+        .output()
+        .expect("could not run the interpreter");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Circular import of 'circular_a.lox'.")
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn imports_from_stdin_are_relative_to_the_working_directory() {
+    let output = run_with_stdin(
+        &[],
+        "import \"tests/spec/modules/constants.lox\";\nprint(name);\n",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a module\n");
+}
+
+#[test]
+fn missing_modules_are_an_error() {
+    let output = run_with_stdin(&[], "import \"missing.lox\";\n");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Could not find module 'missing.lox'.")
+    );
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn syntax_errors_of_modules_are_reported() {
+    let module = Path::new(env!("CARGO_TARGET_TMPDIR")).join("broken_module.lox");
+    fs::write(&module, "var = 1;\n").unwrap();
+    let output = run_with_stdin(&[], &format!("import {:?};\n", module));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Could not parse module"));
+    assert!(stderr.contains("Error at '=': Expect variable name."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn unexported_globals_are_private_to_their_module() {
+    let output = run_with_stdin(
+        &[],
+        "import \"tests/spec/modules/greeting.lox\";\nprint(private);\n",
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "running greeting.lox\n"
+    );
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undefined variable"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn aliased_imports_only_define_prefixed_names() {
+    let output = run_with_stdin(
+        &[],
+        "import \"tests/spec/modules/constants.lox\" as c;\nprint(c.name);\nprint(name);\n",
+    );
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a module\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undefined variable 'name'."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn only_top_level_declarations_can_be_exported() {
+    let output = run_with_stdin(&[], "{ export var a = 1; }\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Error at 'export': Can only export top-level declarations."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn undefined_variables_are_named_in_errors() {
+    let output = run_with_stdin(&[], "print(missing);\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undefined variable 'missing'."));
+    let output = run_with_stdin(&[], "missing = 1;\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Undefined variable 'missing'."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn in_needs_a_collection() {
+    let output = run_with_stdin(&[], "print(1 in 2);\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("right operand of 'in' must be a collection"));
+    assert_eq!(output.status.code(), Some(65));
+    let output = run_with_stdin(&[], "print(1 not in \"1\");\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("only strings can be searched for in a string"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn bitwise_operators_need_numbers() {
+    let output = run_with_stdin(&[], "print(1 & nil);\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("bitwise operands must be numbers"));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn shifts_must_be_in_range() {
+    for source in ["print(1 << -1);\n", "print(1 >> 64);\n"] {
+        let output = run_with_stdin(&[], source);
+        assert!(String::from_utf8_lossy(&output.stderr).contains("shift amount out of range"));
+        assert_eq!(output.status.code(), Some(65));
+    }
+}
+
+#[test]
+fn repeat_counts_must_be_non_negative_integers() {
+    for source in ["repeat -1 { }\n", "repeat 1.5 { }\n", "repeat nil { }\n"] {
+        let output = run_with_stdin(&[], source);
+        assert!(String::from_utf8_lossy(&output.stderr)
+            .contains("repeat count must be a non-negative integer"));
+        assert_eq!(output.status.code(), Some(65));
+    }
+}
+
+#[test]
+fn tuples_are_immutable() {
+    let output = run_with_stdin(&[], "var pair = (1, 2);\npair[0] = 3;\n");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("[<stdin>:2, col 12] Error at ';': Invalid assignment target."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn tuple_indices_must_be_in_range() {
+    for (source, error) in [
+        (
+            "print((1, 2)[2]);\n",
+            "tuple index 2 out of range for 2 elements",
+        ),
+        (
+            "print((1, 2)[-1]);\n",
+            "tuple index -1 out of range for 2 elements",
+        ),
+        (
+            "print((1, 2)[0.5]);\n",
+            "tuple index 0.5 out of range for 2 elements",
+        ),
+        (
+            "print((1, 2)[\"0\"]);\n",
+            "tuple index must be a number, got string",
+        ),
+        (
+            "print(\"ab\"[0]);\n",
+            "only tuples can be indexed, got string",
+        ),
+    ] {
+        let output = run_with_stdin(&[], source);
+        assert!(String::from_utf8_lossy(&output.stderr).contains(error));
+        assert_eq!(output.status.code(), Some(65));
+    }
+}
+
+#[test]
+fn assertions_with_parentheses_are_not_tuples() {
+    let output = run_with_stdin(&[], "assert(false, \"m\");\nprint(\"passed\");\n");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "");
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Assert takes `condition, message` without parentheses."));
+    assert_eq!(output.status.code(), Some(65));
+}
+
+#[test]
+fn version_is_the_package_version() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg("--version")
+        .output()
+        .expect("could not run the interpreter");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let version = stdout.trim_end().strip_prefix("lox ").unwrap();
+    assert_eq!(version, env!("CARGO_PKG_VERSION"));
+    let parts: Vec<&str> = version.split('.').collect();
+    assert_eq!(parts.len(), 3);
+    assert!(parts
+        .iter()
+        .all(|part| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())));
+    assert!(output.status.success());
+}
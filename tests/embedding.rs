@@ -0,0 +1,116 @@
+//! Embedding the interpreter in a Rust program, and exposing host values and functions to Lox.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use lox::interpreter::Interpret;
+use lox::interpreter::Interpreter;
+use lox::interpreter::Signal;
+use lox::lox::LoxErrorKind;
+use lox::lox::LoxRunError;
+use lox::lox::RunOptions;
+use lox::object::LoxObject;
+use lox::parser::Parser;
+
+fn run(interpreter: &mut Interpreter, source: &str) -> Result<(), String> {
+    for statement in Parser::from_source(source).parse().0 {
+        match statement.evaluate(interpreter) {
+            Ok(()) => {}
+            Err(Signal::Error(error)) => return Err(error.0),
+            Err(signal) => panic!("unexpected {:?}", signal),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn host_values_can_be_defined_as_globals() {
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    interpreter.define_global("answer", LoxObject::Number(42.0));
+    run(&mut interpreter, "assert_eq(42, answer, \"answer\");").unwrap();
+}
+
+#[test]
+fn host_functions_can_be_called() {
+    let path = Path::new(env!("CARGO_TARGET_TMPDIR")).join("embedding.txt");
+    fs::write(&path, "written by the host").unwrap();
+
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    interpreter.define_native_fn("read_file", 1, |arguments| match &arguments[0] {
+        LoxObject::String(path) => fs::read_to_string(path)
+            .map(LoxObject::String)
+            .map_err(|error| error.to_string()),
+        _ => Err("read_file() expects a path.".to_string()),
+    });
+    interpreter.define_global(
+        "path",
+        LoxObject::String(path.to_string_lossy().into_owned()),
+    );
+
+    run(
+        &mut interpreter,
+        "assert_eq(\"written by the host\", read_file(path), \"content\");",
+    )
+    .unwrap();
+    assert_eq!(
+        run(&mut interpreter, "read_file(nil);"),
+        Err("read_file() expects a path.".to_string())
+    );
+}
+
+#[test]
+fn missing_files_are_io_errors() {
+    let result = lox::lox::run_file("tests/spec/does_not_exist.lox", &RunOptions::default());
+    assert!(matches!(result, Err(LoxRunError::Io(_))));
+}
+
+#[test]
+fn files_with_errors_return_them() {
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("syntax_error.lox");
+    fs::write(&program, "print(1;\nvar = 2;").unwrap();
+    let Err(LoxRunError::Lox(errors)) =
+        lox::lox::run_file(program.to_str().unwrap(), &RunOptions::default())
+    else {
+        panic!("expected the errors of the program");
+    };
+    assert_eq!(errors.len(), 2);
+    assert!(errors.iter().all(|error| error.kind == LoxErrorKind::Parse));
+}
+
+#[test]
+fn files_run_after_a_file_with_errors() {
+    let directory = Path::new(env!("CARGO_TARGET_TMPDIR"));
+    let first = directory.join("first_has_errors.lox");
+    let second = directory.join("second_runs.lox");
+    fs::write(&first, "print(1;").unwrap();
+    fs::write(&second, "assert false, \"second ran\";").unwrap();
+    assert!(lox::lox::run_file(first.to_str().unwrap(), &RunOptions::default()).is_err());
+    let Err(LoxRunError::Lox(errors)) =
+        lox::lox::run_file(second.to_str().unwrap(), &RunOptions::default())
+    else {
+        panic!("expected the second file to run");
+    };
+    assert_eq!(errors[0].kind, LoxErrorKind::Runtime);
+    assert_eq!(errors[0].message, "Assertion failed: second ran");
+}
+
+#[test]
+fn top_level_syntax_errors_are_reported() {
+    let program = Path::new(env!("CARGO_TARGET_TMPDIR")).join("top_level_error.lox");
+    fs::write(&program, "var a = 1;\na + ;\nprint(a);").unwrap();
+    let Err(LoxRunError::Lox(errors)) =
+        lox::lox::run_file(program.to_str().unwrap(), &RunOptions::default())
+    else {
+        panic!("expected the errors of the program");
+    };
+    assert!(lox::lox::had_error());
+    assert_eq!(errors[0].source, program.to_str().unwrap());
+    assert_eq!(
+        errors[0].to_string(),
+        format!(
+            "[{}:2, col 5] Error at ';': Expected expression.",
+            program.display()
+        )
+    );
+}
@@ -0,0 +1,90 @@
+//! Native functions defined with the `#[lox_native]` attribute.
+
+use std::io;
+
+use lox::interpreter::Interpret;
+use lox::interpreter::Interpreter;
+use lox::interpreter::Signal;
+use lox::lox_native;
+use lox::object::LoxObject;
+use lox::parser::Parser;
+
+#[lox_native("half")]
+fn half(n: f64) -> f64 {
+    n / 2.0
+}
+
+#[lox_native("shout")]
+fn shout(text: String, loud: bool) -> String {
+    if loud {
+        text.to_uppercase()
+    } else {
+        text
+    }
+}
+
+#[lox_native("type_of")]
+fn type_of(value: LoxObject) -> String {
+    value.type_name().to_string()
+}
+
+#[lox_native("checked_sqrt")]
+fn checked_sqrt(n: f64) -> Result<f64, String> {
+    if n < 0.0 {
+        Err(format!("cannot take the square root of {}", n))
+    } else {
+        Ok(n.sqrt())
+    }
+}
+
+fn run(source: &str) -> Result<(), String> {
+    let mut interpreter = Interpreter::with_output(Box::new(io::sink()));
+    register_half(&mut interpreter);
+    register_shout(&mut interpreter);
+    register_type_of(&mut interpreter);
+    register_checked_sqrt(&mut interpreter);
+    for statement in Parser::from_source(source).parse().0 {
+        match statement.evaluate(&mut interpreter) {
+            Ok(()) => {}
+            Err(Signal::Error(error)) => return Err(error.0),
+            Err(signal) => panic!("unexpected {:?}", signal),
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn arguments_and_results_are_converted() {
+    run("assert_eq(1.5, half(3), \"half\");").unwrap();
+    run("assert_eq(\"LOX\", shout(\"lox\", true), \"shout\");").unwrap();
+    run("assert_eq(\"nil\", type_of(nil), \"type_of\");").unwrap();
+    run("assert_eq(3, checked_sqrt(9), \"checked_sqrt\");").unwrap();
+}
+
+#[test]
+fn arity_is_checked() {
+    assert_eq!(
+        run("shout(\"lox\");"),
+        Err("'shout' expected 2 arguments but got 1.".to_string())
+    );
+}
+
+#[test]
+fn wrong_argument_types_are_errors() {
+    assert_eq!(
+        run("half(\"3\");"),
+        Err("half(): Expected number, got string.".to_string())
+    );
+    assert_eq!(
+        run("shout(\"lox\", nil);"),
+        Err("shout(): Expected boolean, got nil.".to_string())
+    );
+}
+
+#[test]
+fn returned_errors_fail_the_call() {
+    assert_eq!(
+        run("checked_sqrt(-4);"),
+        Err("cannot take the square root of -4".to_string())
+    );
+}
@@ -0,0 +1,187 @@
+//! Runs the test programs from the reference Lox implementation
+//! (https://github.com/munificent/craftinginterpreters, `test/` directory).
+//!
+//! The suite is not vendored: point `LOX_OFFICIAL_SUITE` at a checkout of that `test/` directory,
+//! or run `make test-official` which fetches it first. The test is ignored by a plain
+//! `cargo test`, so that it shows as skipped rather than passed.
+//!
+//! Every `// expect: <line>` comment in a program is a line it must print. Programs that expect an
+//! error (`// expect runtime error: ...` or `// Error ...`) must make the interpreter exit with a
+//! failure; the messages themselves are not compared since ours are worded differently.
+//!
+//! `print` is a native function here rather than a statement, so `print x;` in the programs is
+//! rewritten to `print(x);` before running them.
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// Categories (sub-directories of the suite) and single programs using features that are not
+/// implemented yet.
+const EXCLUDED: &[&str] = &[
+    "benchmark",
+    "call",
+    "class",
+    "closure",
+    "constructor",
+    "expressions",
+    "field",
+    "function",
+    "inheritance",
+    "limit",
+    "method",
+    "regression",
+    "return",
+    "scanning",
+    "super",
+    "this",
+    "for/closure_in_body.lox",
+    "for/return_closure.lox",
+    "for/return_inside.lox",
+    "for/syntax.lox",
+    "while/closure_in_body.lox",
+    "while/return_closure.lox",
+    "while/return_inside.lox",
+    "while/syntax.lox",
+];
+
+struct Expectations {
+    output: String,
+    error: bool,
+}
+
+fn expectations(source: &str) -> Expectations {
+    let mut output = String::new();
+    let mut error = false;
+    for line in source.lines() {
+        if let Some((_, expected)) = line.split_once("// expect: ") {
+            output.push_str(expected);
+            output.push('\n');
+        }
+        if line.contains("// expect runtime error:") || line.contains("// Error") {
+            error = true;
+        }
+        if line.contains("// [line") || line.contains("// [java line") {
+            error = true;
+        }
+    }
+    Expectations { output, error }
+}
+
+/// Rewrites `print` statements into calls to the native `print` function.
+fn call_print(source: &str) -> String {
+    let mut rewritten = String::new();
+    let mut rest = source;
+    while let Some(start) = rest.find("print ") {
+        let is_keyword = rest[..start]
+            .chars()
+            .next_back()
+            .is_none_or(|c| !c.is_alphanumeric() && c != '_');
+        rewritten.push_str(&rest[..start]);
+        rest = &rest[start + "print ".len()..];
+        if !is_keyword {
+            rewritten.push_str("print ");
+            continue;
+        }
+        // the printed expression ends at the first semicolon outside of a string
+        let mut in_string = false;
+        let end = rest
+            .find(|c| {
+                if c == '"' {
+                    in_string = !in_string;
+                }
+                c == ';' && !in_string
+            })
+            .unwrap_or(rest.len());
+        rewritten.push_str(&format!("print({})", &rest[..end]));
+        rest = &rest[end..];
+    }
+    rewritten.push_str(rest);
+    rewritten
+}
+
+fn is_excluded(suite: &Path, program: &Path) -> bool {
+    let relative = program.strip_prefix(suite).unwrap();
+    let relative = relative.to_string_lossy().replace('\\', "/");
+    EXCLUDED
+        .iter()
+        .any(|excluded| relative == *excluded || relative.starts_with(&format!("{}/", excluded)))
+}
+
+fn collect_programs(dir: &Path, programs: &mut Vec<PathBuf>) {
+    for entry in fs::read_dir(dir).expect("could not read the official suite") {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            collect_programs(&path, programs);
+        } else if path.extension().is_some_and(|extension| extension == "lox") {
+            programs.push(path);
+        }
+    }
+}
+
+#[test]
+fn print_statements_become_calls() {
+    assert_eq!(call_print("print 1 + 2;"), "print(1 + 2);");
+    assert_eq!(
+        call_print("print \"a;b\"; // print"),
+        "print(\"a;b\"); // print"
+    );
+    assert_eq!(call_print("var blueprint = 1;"), "var blueprint = 1;");
+}
+
+#[test]
+#[ignore = "needs the official suite, run it with `make test-official`"]
+fn official_suite() {
+    let suite = env::var("LOX_OFFICIAL_SUITE")
+        .expect("LOX_OFFICIAL_SUITE must point at the `test/` directory of the official suite");
+    let suite = PathBuf::from(suite);
+
+    let mut programs = vec![];
+    collect_programs(&suite, &mut programs);
+    programs.sort();
+
+    let rewritten_dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("official_suite");
+
+    let mut passed = 0;
+    let mut skipped = 0;
+    let mut failures = vec![];
+    for program in &programs {
+        if is_excluded(&suite, program) {
+            skipped += 1;
+            continue;
+        }
+        let source = fs::read_to_string(program).unwrap();
+        let expected = expectations(&source);
+        let rewritten = rewritten_dir.join(program.strip_prefix(&suite).unwrap());
+        fs::create_dir_all(rewritten.parent().unwrap()).unwrap();
+        fs::write(&rewritten, call_print(&source)).unwrap();
+        let output = Command::new(env!("CARGO_BIN_EXE_lox"))
+            .arg(&rewritten)
+            .output()
+            .expect("could not run the interpreter");
+        let actual = String::from_utf8_lossy(&output.stdout);
+        if actual == expected.output && output.status.success() != expected.error {
+            passed += 1;
+        } else {
+            failures.push(format!(
+                "--- {}\nexpected{}:\n{}\nactual ({}):\n{}{}",
+                program.display(),
+                if expected.error { " an error" } else { "" },
+                expected.output,
+                output.status,
+                actual,
+                String::from_utf8_lossy(&output.stderr),
+            ));
+        }
+    }
+
+    eprintln!(
+        "official suite: {} passed, {} failed, {} skipped",
+        passed,
+        failures.len(),
+        skipped
+    );
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+}
@@ -0,0 +1,58 @@
+//! Runs every program in `tests/spec` and compares what it prints with the `.expected` file next to
+//! it.
+
+use std::fs;
+use std::path::Path;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn spec_programs() -> Vec<PathBuf> {
+    let spec_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("spec");
+    let mut programs: Vec<PathBuf> = fs::read_dir(spec_dir)
+        .expect("could not read tests/spec")
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "lox"))
+        .collect();
+    programs.sort();
+    programs
+}
+
+fn run_program(program: &Path) -> Vec<u8> {
+    Command::new(env!("CARGO_BIN_EXE_lox"))
+        .arg(program)
+        .output()
+        .expect("could not run the interpreter")
+        .stdout
+}
+
+#[test]
+fn spec() {
+    let programs = spec_programs();
+    assert!(!programs.is_empty(), "no programs found in tests/spec");
+
+    let mut failures = vec![];
+    for program in &programs {
+        let expected_path = program.with_extension("expected");
+        let expected = fs::read_to_string(&expected_path)
+            .unwrap_or_else(|_| panic!("missing {}", expected_path.display()));
+        let actual = String::from_utf8_lossy(&run_program(program)).into_owned();
+        if actual != expected {
+            failures.push(format!(
+                "--- {}\nexpected:\n{}\nactual:\n{}",
+                program.display(),
+                expected,
+                actual
+            ));
+        }
+    }
+
+    assert!(
+        failures.is_empty(),
+        "{} of {} programs failed:\n{}",
+        failures.len(),
+        programs.len(),
+        failures.join("\n")
+    );
+}